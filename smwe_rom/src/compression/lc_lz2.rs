@@ -1,8 +1,19 @@
-use std::convert::TryFrom;
-
 use num_enum::TryFromPrimitive;
 
-use crate::error::DecompressionError;
+use crate::{
+    compression::{
+        find_repeats,
+        lz_header_size,
+        lz_run_length,
+        read_lz_header,
+        write_lz_header,
+        Decoder,
+        Decompressed,
+        RepeatKind,
+        LZ_MAX_CHUNK_LENGTH,
+    },
+    error::DecompressionError,
+};
 
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, TryFromPrimitive)]
@@ -34,71 +45,110 @@ enum Command {
     LongLength     = 0b111,
 }
 
-pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecompressionError> {
-    assert!(!input.is_empty());
-    let mut output = Vec::with_capacity(input.len() * 2);
-    let mut in_it = input;
-    while let Some(chunk_header) = in_it.first().copied() {
-        if chunk_header == 0xFF {
-            break;
-        }
-        in_it = &in_it[1..];
-        let command_bits = (chunk_header >> 5) & 0b00000111;
-        let length = chunk_header & 0b00011111;
-
-        let mut command = Command::try_from(command_bits).map_err(|_| DecompressionError("Reading command"))?;
-        let mut length = length as usize + 1;
-
-        if let Command::LongLength = command {
-            let real_command_bits = (chunk_header & 0b00011100) >> 2;
-            command = Command::try_from(real_command_bits).map_err(|_| DecompressionError("Reading long command"))?;
-            let length_part_1 = chunk_header & 0b00000011;
-            let length_part_2 = *in_it.first().ok_or(DecompressionError("Reading long length"))?;
-            length = (((length_part_1 as usize) << 8) | (length_part_2 as usize)) + 1;
-            in_it = &in_it[1..];
-        }
-
+pub fn decompress(input: &[u8]) -> Result<Decompressed, DecompressionError> {
+    let mut decoder = Decoder::new(input);
+    while let Some((command, length)) = read_lz_header(&mut decoder)? {
         use Command::*;
         match command {
             DirectCopy => {
-                let (bytes, rest) = in_it.split_at(length);
-                output.extend_from_slice(bytes);
-                in_it = rest;
+                let bytes = decoder.read_bytes(length, "Reading bytes to copy")?;
+                decoder.output.extend_from_slice(bytes);
             }
             ByteFill => {
-                let byte = *in_it.first().ok_or(DecompressionError("Reading byte to fill"))?;
-                output.resize(output.len() + length, byte);
-                in_it = &in_it[1..];
+                let byte = decoder.read_byte("Reading byte to fill")?;
+                decoder.output.resize(decoder.output.len() + length, byte);
             }
             WordFill => {
-                let (bytes, rest) = in_it.split_at(2);
-                output.extend(bytes.iter().cycle().take(length));
-                in_it = rest;
+                let bytes = decoder.read_bytes(2, "Reading word to fill")?;
+                decoder.output.extend(bytes.iter().cycle().take(length));
             }
             IncreasingFill => {
-                let mut byte = *in_it.first().ok_or(DecompressionError("Reading byte to increasingly fill"))?;
-                output.extend(
-                    std::iter::repeat_with(|| {
-                        let temp = byte;
-                        byte = byte.wrapping_add(1);
-                        temp
-                    })
-                    .take(length),
-                );
-                in_it = &in_it[1..];
+                let byte = decoder.read_byte("Reading byte to increasingly fill")?;
+                decoder.output.extend((0..length).map(|i| byte.wrapping_add(i as u8)));
             }
             Repeat => {
-                let (bytes, rest) = in_it.split_at(2);
+                let bytes = decoder.read_bytes(2, "Reading address to repeat from")?;
                 let read_start = ((bytes[0] as usize) << 8) | (bytes[1] as usize);
-                let write_start = output.len();
-                output.resize(output.len() + length, 0);
-                output.copy_within(read_start..(read_start + length), write_start);
-                in_it = rest;
+                if read_start >= decoder.output.len() {
+                    return Err(decoder.error("Repeating from beyond the output"));
+                }
+                // Copying byte by byte, so that overlapping ranges behave like on the SNES.
+                for i in read_start..read_start + length {
+                    let byte = decoder.output[i];
+                    decoder.output.push(byte);
+                }
             }
-            LongLength => return Err(DecompressionError("Double long length command")),
+            LongLength => return Err(decoder.error("Double long length command")),
         }
     }
 
-    output.shrink_to_fit();
-    Ok(output)
+    Ok(decoder.finish())
+}
+
+/// Finds the smallest sequence of chunks that decompresses back into `input`, using every command
+/// except for LongLength as the real command, and terminates it with 0xFF.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let n = input.len();
+    let matches = find_repeats(input, RepeatKind::Forward, MAX_REPEAT_ADDRESS, 0);
+
+    // best_size[i] is the size of the smallest encoding of input[i..], and best_chunk[i] is the chunk
+    // that encoding starts with.
+    let mut best_size = vec![usize::MAX; n + 1];
+    let mut best_chunk = vec![(Command::DirectCopy, 0, 0); n];
+    best_size[n] = 0;
+
+    for i in (0..n).rev() {
+        let rest = &input[i..];
+        let mut candidates = vec![
+            (Command::DirectCopy, rest.len(), 0),
+            (Command::ByteFill, lz_run_length(rest, |k| rest[k] == rest[0]), 0),
+            (Command::WordFill, lz_run_length(rest, |k| rest[k] == rest[k % 2]), 0),
+            (Command::IncreasingFill, lz_run_length(rest, |k| rest[k] == rest[0].wrapping_add(k as u8)), 0),
+        ];
+        if let Some((source, length)) = matches[i].longest {
+            candidates.push((Command::Repeat, length, source));
+        }
+
+        for (command, max_length, source) in candidates {
+            for length in 1..=max_length.min(LZ_MAX_CHUNK_LENGTH) {
+                let size = lz_header_size(length) + payload_size(command, length) + best_size[i + length];
+                if size < best_size[i] {
+                    best_size[i] = size;
+                    best_chunk[i] = (command, length, source);
+                }
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(best_size[0] + 1);
+    let mut i = 0;
+    while i < n {
+        let (command, length, source) = best_chunk[i];
+        write_lz_header(&mut output, command as u8, length);
+        match command {
+            Command::DirectCopy => output.extend_from_slice(&input[i..i + length]),
+            Command::ByteFill | Command::IncreasingFill => output.push(input[i]),
+            Command::WordFill => {
+                output.push(input[i]);
+                output.push(*input.get(i + 1).unwrap_or(&input[i]));
+            }
+            Command::Repeat => output.extend_from_slice(&[(source >> 8) as u8, source as u8]),
+            Command::LongLength => unreachable!("LongLength is not a chunk's real command"),
+        }
+        i += length;
+    }
+    output.push(0xFF);
+
+    output
+}
+
+const MAX_REPEAT_ADDRESS: usize = 0xFFFF;
+
+fn payload_size(command: Command, length: usize) -> usize {
+    match command {
+        Command::DirectCopy => length,
+        Command::ByteFill | Command::IncreasingFill => 1,
+        Command::WordFill | Command::Repeat => 2,
+        Command::LongLength => unreachable!("LongLength is not a chunk's real command"),
+    }
 }
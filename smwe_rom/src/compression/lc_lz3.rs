@@ -0,0 +1,226 @@
+use num_enum::TryFromPrimitive;
+
+use crate::{
+    compression::{
+        find_repeats,
+        lz_header_size,
+        lz_run_length,
+        read_lz_header,
+        write_lz_header,
+        Decoder,
+        Decompressed,
+        RepeatKind,
+        LZ_MAX_CHUNK_LENGTH,
+    },
+    error::DecompressionError,
+};
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, TryFromPrimitive)]
+enum Command {
+    /// Followed by (L+1) bytes of data
+    DirectCopy        = 0b000,
+
+    /// Followed by one byte to be repeated (L+1) times
+    ByteFill          = 0b001,
+
+    /// Followed by two bytes. Output first byte, then second, then first,
+    /// then second, etc. until (L+1) bytes has been outputted
+    WordFill          = 0b010,
+
+    /// Output (L+1) zero bytes, not followed by any data
+    ZeroFill          = 0b011,
+
+    /// Followed by an address (see below) in the output buffer to copy
+    /// (L+1) bytes from
+    Repeat            = 0b100,
+
+    /// Same as Repeat, but the bits of each copied byte are reversed
+    BitReversedRepeat = 0b101,
+
+    /// Same as Repeat, but the bytes are copied going backwards from the
+    /// address
+    BackwardsRepeat   = 0b110,
+
+    /// This command has got a two-byte header:
+    /// ```text
+    /// 111CCCLL LLLLLLLL
+    /// CCC:        Real command
+    /// LLLLLLLLLL: Length
+    /// ```
+    LongLength        = 0b111,
+}
+
+// Addresses of the repeat commands are either one byte long:
+//   1DDDDDDD: relative, (D+1) bytes behind the current end of the output buffer
+// or two bytes long:
+//   0AAAAAAA AAAAAAAA: absolute, from the beginning of the output buffer
+
+pub fn decompress(input: &[u8]) -> Result<Decompressed, DecompressionError> {
+    let mut decoder = Decoder::new(input);
+    while let Some((command, length)) = read_lz_header(&mut decoder)? {
+        use Command::*;
+        match command {
+            DirectCopy => {
+                let bytes = decoder.read_bytes(length, "Reading bytes to copy")?;
+                decoder.output.extend_from_slice(bytes);
+            }
+            ByteFill => {
+                let byte = decoder.read_byte("Reading byte to fill")?;
+                decoder.output.resize(decoder.output.len() + length, byte);
+            }
+            WordFill => {
+                let bytes = decoder.read_bytes(2, "Reading word to fill")?;
+                decoder.output.extend(bytes.iter().cycle().take(length));
+            }
+            ZeroFill => {
+                decoder.output.resize(decoder.output.len() + length, 0);
+            }
+            Repeat => {
+                let read_start = read_address(&mut decoder)?;
+                for i in read_start..read_start + length {
+                    let byte = decoder.output[i];
+                    decoder.output.push(byte);
+                }
+            }
+            BitReversedRepeat => {
+                let read_start = read_address(&mut decoder)?;
+                for i in read_start..read_start + length {
+                    let byte = decoder.output[i].reverse_bits();
+                    decoder.output.push(byte);
+                }
+            }
+            BackwardsRepeat => {
+                let read_start = read_address(&mut decoder)?;
+                if read_start + 1 < length {
+                    return Err(decoder.error("Repeating backwards from beyond the beginning of the output"));
+                }
+                for i in (read_start + 1 - length..=read_start).rev() {
+                    let byte = decoder.output[i];
+                    decoder.output.push(byte);
+                }
+            }
+            LongLength => return Err(decoder.error("Double long length command")),
+        }
+    }
+
+    Ok(decoder.finish())
+}
+
+/// Finds the smallest sequence of chunks that decompresses back into `input`, using every command
+/// except for LongLength as the real command, and terminates it with 0xFF.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let n = input.len();
+    let repeats = [
+        (Command::Repeat, find_repeats(input, RepeatKind::Forward, MAX_ABSOLUTE_ADDRESS, MAX_RELATIVE_DISTANCE)),
+        (
+            Command::BitReversedRepeat,
+            find_repeats(input, RepeatKind::BitReversed, MAX_ABSOLUTE_ADDRESS, MAX_RELATIVE_DISTANCE),
+        ),
+        (
+            Command::BackwardsRepeat,
+            find_repeats(input, RepeatKind::Backwards, MAX_ABSOLUTE_ADDRESS, MAX_RELATIVE_DISTANCE),
+        ),
+    ];
+
+    // best_size[i] is the size of the smallest encoding of input[i..], and best_chunk[i] is the chunk
+    // that encoding starts with.
+    let mut best_size = vec![usize::MAX; n + 1];
+    let mut best_chunk = vec![(Command::DirectCopy, 0, 0); n];
+    best_size[n] = 0;
+
+    for i in (0..n).rev() {
+        let rest = &input[i..];
+        let mut candidates = vec![
+            (Command::DirectCopy, rest.len(), 0),
+            (Command::ByteFill, lz_run_length(rest, |k| rest[k] == rest[0]), 0),
+            (Command::WordFill, lz_run_length(rest, |k| rest[k] == rest[k % 2]), 0),
+            (Command::ZeroFill, lz_run_length(rest, |k| rest[k] == 0), 0),
+        ];
+        for (command, matches) in repeats.iter() {
+            let found = matches[i];
+            for (source, length) in found.longest.into_iter().chain(found.longest_near) {
+                candidates.push((*command, length, source));
+            }
+        }
+
+        for (command, max_length, source) in candidates {
+            for length in 1..=max_length.min(LZ_MAX_CHUNK_LENGTH) {
+                let size = lz_header_size(length) + payload_size(command, length, i, source) + best_size[i + length];
+                if size < best_size[i] {
+                    best_size[i] = size;
+                    best_chunk[i] = (command, length, source);
+                }
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(best_size[0] + 1);
+    let mut i = 0;
+    while i < n {
+        let (command, length, source) = best_chunk[i];
+        write_lz_header(&mut output, command as u8, length);
+        match command {
+            Command::DirectCopy => output.extend_from_slice(&input[i..i + length]),
+            Command::ByteFill => output.push(input[i]),
+            Command::WordFill => {
+                output.push(input[i]);
+                output.push(*input.get(i + 1).unwrap_or(&input[i]));
+            }
+            Command::ZeroFill => {}
+            Command::Repeat | Command::BitReversedRepeat | Command::BackwardsRepeat => {
+                if i - source <= MAX_RELATIVE_DISTANCE {
+                    output.push(0x80 | (i - source - 1) as u8);
+                } else {
+                    output.extend_from_slice(&[(source >> 8) as u8, source as u8]);
+                }
+            }
+            Command::LongLength => unreachable!("LongLength is not a chunk's real command"),
+        }
+        i += length;
+    }
+    output.push(0xFF);
+
+    output
+}
+
+// -------------------------------------------------------------------------------------------------
+
+const MAX_ABSOLUTE_ADDRESS: usize = 0x7FFF;
+const MAX_RELATIVE_DISTANCE: usize = 0x80;
+
+fn read_address(decoder: &mut Decoder<Command>) -> Result<usize, DecompressionError> {
+    let first = decoder.read_byte("Reading address to repeat from")?;
+    let address = if (first & 0x80) != 0 {
+        let distance = (first & 0x7F) as usize + 1;
+        decoder
+            .output
+            .len()
+            .checked_sub(distance)
+            .ok_or_else(|| decoder.error("Repeating from before the beginning of the output"))?
+    } else {
+        let second = decoder.read_byte("Reading address to repeat from")?;
+        ((first as usize) << 8) | (second as usize)
+    };
+    if address >= decoder.output.len() {
+        return Err(decoder.error("Repeating from beyond the output"));
+    }
+    Ok(address)
+}
+
+fn payload_size(command: Command, length: usize, position: usize, source: usize) -> usize {
+    match command {
+        Command::DirectCopy => length,
+        Command::ZeroFill => 0,
+        Command::ByteFill => 1,
+        Command::WordFill => 2,
+        Command::Repeat | Command::BitReversedRepeat | Command::BackwardsRepeat => {
+            if position - source <= MAX_RELATIVE_DISTANCE {
+                1
+            } else {
+                2
+            }
+        }
+        Command::LongLength => unreachable!("LongLength is not a chunk's real command"),
+    }
+}
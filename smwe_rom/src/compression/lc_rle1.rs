@@ -2,7 +2,10 @@ use std::convert::TryFrom;
 
 use num_enum::TryFromPrimitive;
 
-use crate::error::DecompressionError;
+use crate::{
+    compression::{Decoder, Decompressed},
+    error::DecompressionError,
+};
 
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, TryFromPrimitive)]
@@ -11,34 +14,84 @@ enum Command {
     ByteFill   = 1,
 }
 
-pub fn decompress(input: &[u8]) -> Result<Vec<u8>, DecompressionError> {
-    assert!(!input.is_empty());
-    assert!(!input.len() >= 2);
-    let mut output = Vec::with_capacity(input.len() * 2);
-    let mut in_it = input;
-    while let Some(chunk_header) = in_it.first().copied() {
-        if chunk_header == 0xFF && (in_it.len() == 1 || in_it[1] == 0xFF) {
+pub fn decompress(input: &[u8]) -> Result<Decompressed, DecompressionError> {
+    let mut decoder = Decoder::new(input);
+    loop {
+        decoder.command = None;
+        let chunk_header = decoder.read_byte("Missing end of data marker")?;
+        if chunk_header == 0xFF && decoder.peek(0).unwrap_or(0xFF) == 0xFF {
+            decoder.offset = (decoder.offset + 1).min(input.len());
             break;
         }
-        in_it = &in_it[1..];
         let command = (chunk_header >> 7) & 1;
-        let command = Command::try_from(command).map_err(|_| DecompressionError("Reading command"))?;
+        let command = Command::try_from(command).map_err(|_| decoder.error("Reading command"))?;
         let length = (chunk_header & 0b01111111) as usize + 1;
+        decoder.command = Some(command);
 
         match command {
             Command::DirectCopy => {
-                let (bytes, rest) = in_it.split_at(length);
-                output.extend_from_slice(bytes);
-                in_it = rest;
+                let bytes = decoder.read_bytes(length, "Reading bytes to copy")?;
+                decoder.output.extend_from_slice(bytes);
             }
             Command::ByteFill => {
-                let byte = *in_it.first().ok_or(DecompressionError("Reading byte to fill"))?;
-                output.resize(output.len() + length, byte);
-                in_it = &in_it[1..];
+                let byte = decoder.read_byte("Reading byte to fill")?;
+                decoder.output.resize(decoder.output.len() + length, byte);
             }
         }
     }
 
-    output.shrink_to_fit();
-    Ok(output)
+    Ok(decoder.finish())
 }
+
+/// Finds the smallest sequence of DirectCopy and ByteFill chunks that decompresses back into `input`,
+/// and terminates it with two 0xFF bytes.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let n = input.len();
+
+    // best_size[i] is the size of the smallest encoding of input[i..], and best_chunk[i] is the chunk
+    // that encoding starts with.
+    let mut best_size = vec![usize::MAX; n + 1];
+    let mut best_chunk = vec![(Command::DirectCopy, 0); n];
+    best_size[n] = 0;
+
+    for i in (0..n).rev() {
+        let rest = &input[i..];
+
+        // A 128-byte fill of 0xFF would be encoded as 0xFF 0xFF, which reads as the terminator.
+        let max_fill_length = if rest[0] == 0xFF { MAX_CHUNK_LENGTH - 1 } else { MAX_CHUNK_LENGTH };
+        let fill_length = rest.iter().take(max_fill_length).take_while(|&&b| b == rest[0]).count();
+        let copy_length = rest.len().min(MAX_CHUNK_LENGTH);
+
+        for length in 1..=copy_length {
+            let size = 1 + length + best_size[i + length];
+            if size < best_size[i] {
+                best_size[i] = size;
+                best_chunk[i] = (Command::DirectCopy, length);
+            }
+        }
+        for length in 1..=fill_length {
+            let size = 2 + best_size[i + length];
+            if size < best_size[i] {
+                best_size[i] = size;
+                best_chunk[i] = (Command::ByteFill, length);
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(best_size[0] + 2);
+    let mut i = 0;
+    while i < n {
+        let (command, length) = best_chunk[i];
+        output.push(((command as u8) << 7) | (length - 1) as u8);
+        match command {
+            Command::DirectCopy => output.extend_from_slice(&input[i..i + length]),
+            Command::ByteFill => output.push(input[i]),
+        }
+        i += length;
+    }
+    output.extend_from_slice(&[0xFF, 0xFF]);
+
+    output
+}
+
+const MAX_CHUNK_LENGTH: usize = 128;
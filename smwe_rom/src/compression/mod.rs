@@ -1,2 +1,242 @@
+use std::{collections::HashMap, convert::TryFrom, fmt::Debug};
+
+use crate::error::DecompressionError;
+
 pub mod lc_lz2;
+pub mod lc_lz3;
 pub mod lc_rle1;
+
+/// Compression formats of the data stored in the ROM.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Codec {
+    LcLz2,
+    LcLz3,
+    LcRle1,
+}
+
+/// Result of decompressing data which starts at the beginning of the input.
+#[derive(Clone, Debug)]
+pub struct Decompressed {
+    /// The decompressed data.
+    pub output:    Vec<u8>,
+    /// Number of input bytes making up the compressed data, including the end of data marker.
+    pub input_len: usize,
+}
+
+impl Codec {
+    pub fn decompress(self, input: &[u8]) -> Result<Decompressed, DecompressionError> {
+        match self {
+            Codec::LcLz2 => lc_lz2::decompress(input),
+            Codec::LcLz3 => lc_lz3::decompress(input),
+            Codec::LcRle1 => lc_rle1::decompress(input),
+        }
+    }
+
+    pub fn compress(self, input: &[u8]) -> Vec<u8> {
+        match self {
+            Codec::LcLz2 => lc_lz2::compress(input),
+            Codec::LcLz3 => lc_lz3::compress(input),
+            Codec::LcRle1 => lc_rle1::compress(input),
+        }
+    }
+}
+
+/// Decompressed data is at most a few dozen kilobytes, while the input often runs until the end of the ROM,
+/// so the output buffer starts small rather than proportional to the input.
+const INITIAL_OUTPUT_CAPACITY: usize = 0x4000;
+
+/// Bounds-checked cursor over compressed data which keeps track of everything needed to report
+/// where decompression has failed.
+pub(crate) struct Decoder<'a, C: Copy + Debug> {
+    input:       &'a [u8],
+    pub offset:  usize,
+    pub command: Option<C>,
+    pub output:  Vec<u8>,
+}
+
+impl<'a, C: Copy + Debug> Decoder<'a, C> {
+    pub fn new(input: &'a [u8]) -> Self {
+        let capacity = (2 * input.len()).min(INITIAL_OUTPUT_CAPACITY);
+        Self { input, offset: 0, command: None, output: Vec::with_capacity(capacity) }
+    }
+
+    pub fn peek(&self, distance: usize) -> Option<u8> {
+        self.input.get(self.offset + distance).copied()
+    }
+
+    pub fn read_byte(&mut self, reason: &'static str) -> Result<u8, DecompressionError> {
+        let byte = self.peek(0).ok_or_else(|| self.error(reason))?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    pub fn read_bytes(&mut self, count: usize, reason: &'static str) -> Result<&'a [u8], DecompressionError> {
+        let bytes = self.input.get(self.offset..self.offset + count).ok_or_else(|| self.error(reason))?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    pub fn error(&self, reason: &'static str) -> DecompressionError {
+        DecompressionError {
+            reason,
+            offset: self.offset,
+            command: self.command.map(|c| format!("{:?}", c)),
+            output_len: self.output.len(),
+        }
+    }
+
+    pub fn finish(mut self) -> Decompressed {
+        self.output.shrink_to_fit();
+        Decompressed { output: self.output, input_len: self.offset }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+// Chunk headers shared by LC_LZ2 and LC_LZ3:
+// CCCLLLLL for lengths up to 32, or 111CCCLL LLLLLLLL for lengths up to 1024.
+
+pub(crate) const LZ_MAX_CHUNK_LENGTH: usize = 1024;
+const LZ_MAX_SHORT_CHUNK_LENGTH: usize = 32;
+const LZ_LONG_LENGTH: u8 = 0b111;
+
+/// Reads a chunk header, returning the real command and the length of the chunk,
+/// or `None` if the end of data marker has been reached.
+pub(crate) fn read_lz_header<C: Copy + Debug + TryFrom<u8>>(
+    decoder: &mut Decoder<C>,
+) -> Result<Option<(C, usize)>, DecompressionError> {
+    decoder.command = None;
+    let chunk_header = decoder.read_byte("Missing end of data marker")?;
+    if chunk_header == 0xFF {
+        return Ok(None);
+    }
+    let command_bits = (chunk_header >> 5) & 0b00000111;
+    let (command, length) = if command_bits == LZ_LONG_LENGTH {
+        decoder.command = C::try_from(LZ_LONG_LENGTH).ok();
+        let real_command_bits = (chunk_header & 0b00011100) >> 2;
+        let command = C::try_from(real_command_bits).map_err(|_| decoder.error("Reading long command"))?;
+        decoder.command = Some(command);
+        let length_part_1 = chunk_header & 0b00000011;
+        let length_part_2 = decoder.read_byte("Reading long length")?;
+        (command, (((length_part_1 as usize) << 8) | (length_part_2 as usize)) + 1)
+    } else {
+        let command = C::try_from(command_bits).map_err(|_| decoder.error("Reading command"))?;
+        (command, (chunk_header & 0b00011111) as usize + 1)
+    };
+    decoder.command = Some(command);
+    Ok(Some((command, length)))
+}
+
+pub(crate) fn lz_header_size(length: usize) -> usize {
+    if length > LZ_MAX_SHORT_CHUNK_LENGTH {
+        2
+    } else {
+        1
+    }
+}
+
+pub(crate) fn write_lz_header(output: &mut Vec<u8>, command_bits: u8, length: usize) {
+    debug_assert!((1..=LZ_MAX_CHUNK_LENGTH).contains(&length));
+    let length = length - 1;
+    if length < LZ_MAX_SHORT_CHUNK_LENGTH {
+        output.push((command_bits << 5) | length as u8);
+    } else {
+        output.push((LZ_LONG_LENGTH << 5) | (command_bits << 2) | (length >> 8) as u8);
+        output.push(length as u8);
+    }
+}
+
+/// Counts how many leading bytes of `input` satisfy `matches_at`, up to the maximum chunk length.
+pub(crate) fn lz_run_length(input: &[u8], matches_at: impl Fn(usize) -> bool) -> usize {
+    (0..input.len().min(LZ_MAX_CHUNK_LENGTH)).take_while(|&k| matches_at(k)).count()
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// The ways in which the repeat commands can read previously decompressed data.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum RepeatKind {
+    Forward,
+    BitReversed,
+    Backwards,
+}
+
+/// The longest repeat found for a position, and the longest one whose source lies within a short
+/// distance behind it.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct RepeatMatches {
+    pub longest:      Option<(usize, usize)>,
+    pub longest_near: Option<(usize, usize)>,
+}
+
+const MIN_MATCH_LENGTH: usize = 3;
+const MAX_MATCH_CANDIDATES: usize = 64;
+
+impl RepeatKind {
+    fn source_byte(self, input: &[u8], source: usize, step: usize) -> Option<u8> {
+        match self {
+            RepeatKind::Forward => input.get(source + step).copied(),
+            RepeatKind::BitReversed => input.get(source + step).map(|b| b.reverse_bits()),
+            RepeatKind::Backwards => source.checked_sub(step).map(|s| input[s]),
+        }
+    }
+
+    fn max_length(self, source: usize, position: usize) -> usize {
+        match self {
+            RepeatKind::Forward | RepeatKind::BitReversed => position - source,
+            RepeatKind::Backwards => source + 1,
+        }
+    }
+}
+
+/// For each position, finds the longest repeats of data preceding it, as read by `kind`. The source
+/// must lie entirely before the position being encoded and start at an address not above `max_address`.
+pub(crate) fn find_repeats(
+    input: &[u8], kind: RepeatKind, max_address: usize, near_distance: usize,
+) -> Vec<RepeatMatches> {
+    let n = input.len();
+    let mut matches = vec![RepeatMatches::default(); n];
+    let mut heads = HashMap::<[u8; MIN_MATCH_LENGTH], usize>::new();
+    let mut prev = vec![usize::MAX; n];
+
+    let source_key = |p: usize| -> Option<[u8; MIN_MATCH_LENGTH]> {
+        Some([kind.source_byte(input, p, 0)?, kind.source_byte(input, p, 1)?, kind.source_byte(input, p, 2)?])
+    };
+
+    for i in 0..n {
+        if let Some((p, key)) = i.checked_sub(1).filter(|&p| p <= max_address).and_then(|p| Some((p, source_key(p)?))) {
+            if let Some(head) = heads.insert(key, p) {
+                prev[p] = head;
+            }
+        }
+        if i + MIN_MATCH_LENGTH > n {
+            continue;
+        }
+
+        let key = [input[i], input[i + 1], input[i + 2]];
+        let longest_possible = (n - i).min(LZ_MAX_CHUNK_LENGTH);
+        let mut found = RepeatMatches::default();
+        let mut candidate = heads.get(&key).copied().unwrap_or(usize::MAX);
+        let mut candidates_left = MAX_MATCH_CANDIDATES;
+        while candidate != usize::MAX && candidates_left > 0 {
+            let max_length = kind.max_length(candidate, i).min(longest_possible);
+            let length =
+                (0..max_length).take_while(|&k| kind.source_byte(input, candidate, k) == Some(input[i + k])).count();
+            if length >= MIN_MATCH_LENGTH {
+                if found.longest.map_or(0, |(_, l)| l) < length {
+                    found.longest = Some((candidate, length));
+                }
+                if i - candidate <= near_distance && found.longest_near.map_or(0, |(_, l)| l) < length {
+                    found.longest_near = Some((candidate, length));
+                }
+            }
+            if found.longest.map_or(0, |(_, l)| l) == longest_possible {
+                break;
+            }
+            candidate = prev[candidate];
+            candidates_left -= 1;
+        }
+        matches[i] = found;
+    }
+
+    matches
+}
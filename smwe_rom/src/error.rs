@@ -1,8 +1,11 @@
 use thiserror::Error;
 
-use crate::snes_utils::{
-    addr::{AddrPc, AddrSnes},
-    rom_slice::{PcSlice, SnesSlice},
+use crate::{
+    internal_header::MapMode,
+    snes_utils::{
+        addr::{AddrPc, AddrSnes},
+        rom_slice::{PcSlice, SnesSlice},
+    },
 };
 
 // -------------------------------------------------------------------------------------------------
@@ -11,17 +14,37 @@ use crate::snes_utils::{
 pub enum AddressError {
     #[error("Invalid PC LoROM address {0:#x}")]
     InvalidPcLoRom(AddrPc),
-    #[error("Invalid PC LoROM address {0:#x}")]
+    #[error("Invalid PC HiROM address {0:#x}")]
     InvalidPcHiRom(AddrPc),
+    #[error("Invalid PC ExLoROM address {0:#x}")]
+    InvalidPcExLoRom(AddrPc),
+    #[error("Invalid PC ExHiROM address {0:#x}")]
+    InvalidPcExHiRom(AddrPc),
+    #[error("Invalid PC SA-1 address {0:#x}")]
+    InvalidPcSa1(AddrPc),
     #[error("Invalid SNES LoROM address {0:#x}")]
     InvalidSnesLoRom(AddrSnes),
-    #[error("Invalid SNES LoROM address {0:#x}")]
+    #[error("Invalid SNES HiROM address {0:#x}")]
     InvalidSnesHiRom(AddrSnes),
+    #[error("Invalid SNES ExLoROM address {0:#x}")]
+    InvalidSnesExLoRom(AddrSnes),
+    #[error("Invalid SNES ExHiROM address {0:#x}")]
+    InvalidSnesExHiRom(AddrSnes),
+    #[error("Invalid SNES SA-1 address {0:#x}")]
+    InvalidSnesSa1(AddrSnes),
 }
 
 #[derive(Debug, Error)]
-#[error("Failed to decompress data: {0}")]
-pub struct DecompressionError(pub &'static str);
+#[error(
+    "Failed to decompress data: {reason} (input offset: {offset:#x}, command: {}, output length: {output_len:#x})",
+    .command.as_deref().unwrap_or("none")
+)]
+pub struct DecompressionError {
+    pub reason:     &'static str,
+    pub offset:     usize,
+    pub command:    Option<String>,
+    pub output_len: usize,
+}
 
 #[derive(Debug, Error)]
 pub enum GfxTileError {
@@ -72,6 +95,8 @@ pub enum InternalHeaderParseError {
     ReadDeveloperId(RomError),
     #[error("Reading Version Number:\n- {0}")]
     ReadVersionNumber(RomError),
+    #[error("Reading Checksum and Complement:\n- {0}")]
+    ReadChecksum(RomError),
 }
 
 #[derive(Debug, Error)]
@@ -113,14 +138,54 @@ pub enum ColorPaletteParseError {
 
 #[derive(Debug, Error)]
 pub enum GfxFileParseError {
+    #[error("Reading GFX file address:\n- {0}")]
+    ReadAddress(RomError),
     #[error("Isolating GFX data:\n- {0}")]
     IsolatingData(RomError),
     #[error("Decompressing GFX data:\n- {0}")]
     DecompressingData(DecompressionError),
+    #[error("Decompressed GFX data takes {0} bytes, which is not a multiple of the tile size {1}")]
+    DecompressedSize(usize, usize),
     #[error("Parsing GFX tile")]
     ParsingTile,
 }
 
+#[derive(Debug, Error)]
+pub enum GfxExportError {
+    #[error("Writing PNG file:\n- {0}")]
+    Io(std::io::Error),
+    #[error("Encoding PNG image:\n- {0}")]
+    Encoding(png::EncodingError),
+}
+
+#[derive(Debug, Error)]
+pub enum GfxSetError {
+    #[error("Reading GFX lists at ${0}:\n- {1}")]
+    Read(SnesSlice, RomError),
+    #[error("Tileset {0:#X} does not exist")]
+    Tileset(u8),
+    #[error("Sprite GFX set {0:#X} does not exist")]
+    SpriteSet(u8),
+    #[error("GFX file {0:#X} does not exist")]
+    File(usize),
+}
+
+#[derive(Debug, Error)]
+pub enum Map16Error {
+    #[error("Reading Map16 blocks at ${0}:\n- {1}")]
+    Read(SnesSlice, RomError),
+    #[error("Writing Map16 blocks at ${0}:\n- {1}")]
+    Write(SnesSlice, RomError),
+    #[error("Map16 table at ${0} holds {1} blocks, but {2} were given")]
+    BlockCount(SnesSlice, usize, usize),
+    #[error("The ROM has {0} tilesets, but tileset-specific blocks of {1} were given")]
+    TilesetCount(usize, usize),
+    #[error("Tilesets {0:#X} and {1:#X} share their tileset-specific blocks, but they were edited differently")]
+    SharedTilesetBlocks(usize, usize),
+    #[error("Value {value:#x} of {field} is out of range, the maximum is {max:#x}")]
+    OutOfRange { field: &'static str, value: u16, max: u16 },
+}
+
 #[derive(Debug, Error)]
 pub enum LevelParseError {
     #[error("Reading address of Layer1:\n- {0}")]
@@ -150,6 +215,52 @@ pub enum LevelParseError {
     SpriteRead(RomError),
 }
 
+#[derive(Debug, Error)]
+pub enum LevelHeaderError {
+    #[error("Value {value:#x} of {field} is out of range, the maximum is {max:#x}")]
+    OutOfRange { field: &'static str, value: u8, max: u8 },
+}
+
+#[derive(Debug, Error)]
+pub enum ObjectError {
+    #[error("Value {value:#x} of {field} is out of range, the maximum is {max:#x}")]
+    OutOfRange { field: &'static str, value: u16, max: u16 },
+    #[error("Standard object 0 is reserved for extended objects")]
+    ReservedStandardObject,
+    #[error("Extended object {0:#x} is reserved for screen exits and screen jumps")]
+    ReservedExtendedObject(u8),
+    #[error("Object index {0} is out of bounds for a layer of {1} objects")]
+    IndexOutOfBounds(usize, usize),
+}
+
+#[derive(Debug, Error)]
+pub enum LevelWriteError {
+    #[error("Reading address of Layer1:\n- {0}")]
+    Layer1AddressRead(RomError),
+    #[error("Reading address of Layer2:\n- {0}")]
+    Layer2AddressRead(RomError),
+    #[error("Reading address of Sprite data:\n- {0}")]
+    SpriteAddressRead(RomError),
+
+    #[error("Allocating space for Layer1 data:\n- {0}")]
+    Layer1Allocate(FreeSpaceError),
+    #[error("Allocating space for Layer2 data:\n- {0}")]
+    Layer2Allocate(FreeSpaceError),
+    #[error("Allocating space for Sprite data:\n- {0}")]
+    SpriteAllocate(FreeSpaceError),
+    #[error("Freeing old level data:\n- {0}")]
+    FreeOldData(FreeSpaceError),
+    #[error("Locating level data:\n- {0}")]
+    Address(AddressError),
+
+    #[error("Writing level data in place:\n- {0}")]
+    DataWrite(RomError),
+    #[error("Writing level data pointers:\n- {0}")]
+    PointerWrite(RomError),
+    #[error("Writing Secondary Header:\n- {0}")]
+    SecondaryHeaderWrite(RomError),
+}
+
 #[derive(Debug, Error)]
 pub enum RomError {
     #[error("Empty ROM file")]
@@ -159,26 +270,122 @@ pub enum RomError {
 
     #[error("Invalid PC slice: {0}")]
     SlicePc(PcSlice),
+    #[error("Invalid SNES slice: {0}")]
+    SliceSnes(SnesSlice),
     #[error("Invalid LoROM slice: {0}")]
     SliceLoRom(SnesSlice),
     #[error("Invalid HiROM slice: {0}")]
     SliceHiRom(SnesSlice),
+    #[error("Invalid ExLoROM slice: {0}")]
+    SliceExLoRom(SnesSlice),
+    #[error("Invalid ExHiROM slice: {0}")]
+    SliceExHiRom(SnesSlice),
+    #[error("Invalid SA-1 slice: {0}")]
+    SliceSa1(SnesSlice),
 
     #[error("Could not parse PC slice: {0}")]
     ParsePc(PcSlice),
+    #[error("Could not parse SNES slice: {0}")]
+    ParseSnes(SnesSlice),
     #[error("Could not parse LoROM slice: {0}")]
     ParseLoRom(SnesSlice),
     #[error("Could not parse HiROM slice: {0}")]
     ParseHiRom(SnesSlice),
+    #[error("Could not parse ExLoROM slice: {0}")]
+    ParseExLoRom(SnesSlice),
+    #[error("Could not parse ExHiROM slice: {0}")]
+    ParseExHiRom(SnesSlice),
+    #[error("Could not parse SA-1 slice: {0}")]
+    ParseSa1(SnesSlice),
 
+    #[error("Address conversion in SNES slicing:\n- {0}")]
+    AddressSliceSnes(AddressError),
     #[error("Address conversion in LoROM slicing:\n- {0}")]
     AddressSliceLoRom(AddressError),
     #[error("Address conversion in HiROM slicing:\n- {0}")]
     AddressSliceHiRom(AddressError),
+    #[error("Address conversion in ExLoROM slicing:\n- {0}")]
+    AddressSliceExLoRom(AddressError),
+    #[error("Address conversion in ExHiROM slicing:\n- {0}")]
+    AddressSliceExHiRom(AddressError),
+    #[error("Address conversion in SA-1 slicing:\n- {0}")]
+    AddressSliceSa1(AddressError),
+    #[error("Address conversion in SNES parsing:\n- {0}")]
+    AddressParseSnes(AddressError),
     #[error("Address conversion in LoROM parsing:\n- {0}")]
     AddressParseLoRom(AddressError),
     #[error("Address conversion in HiROM parsing:\n- {0}")]
     AddressParseHiRom(AddressError),
+    #[error("Address conversion in ExLoROM parsing:\n- {0}")]
+    AddressParseExLoRom(AddressError),
+    #[error("Address conversion in ExHiROM parsing:\n- {0}")]
+    AddressParseExHiRom(AddressError),
+    #[error("Address conversion in SA-1 parsing:\n- {0}")]
+    AddressParseSa1(AddressError),
+}
+
+#[derive(Debug, Error)]
+pub enum ExpansionError {
+    #[error("Cannot expand a {0:#x}-byte ROM to {1:#x} bytes, it must be expanded to 1, 2, 4 or 8 MB")]
+    Size(usize, usize),
+    #[error("Expanding beyond 4 MB is only supported for LoROM, not {0}")]
+    Unsupported(MapMode),
+    #[error("Reading internal header:\n- {0}")]
+    Header(InternalHeaderParseError),
+    #[error("Writing expanded ROM:\n- {0}")]
+    Write(RomError),
+}
+
+#[derive(Debug, Error)]
+pub enum FreeSpaceError {
+    #[error("Cannot allocate {0:#x} bytes, RATS-protected data must be 1 to 0x10000 bytes long")]
+    InvalidSize(usize),
+    #[error("No free block fits {0:#x} bytes of data")]
+    NoSpace(usize),
+    #[error("No RATS tag found before data at {0:#x}")]
+    NoTag(AddrPc),
+    #[error("Writing to free space:\n- {0}")]
+    Write(RomError),
+}
+
+#[derive(Debug, Error)]
+pub enum BpsError {
+    #[error("Missing \"BPS1\" header, this is not a BPS patch")]
+    MissingHeader,
+    #[error("Patch ends unexpectedly at offset {0:#x}")]
+    UnexpectedEnd(usize),
+    #[error("Number at offset {0:#x} is too large")]
+    InvalidNumber(usize),
+    #[error("Patch is corrupted (CRC32 is {actual:08X}, should be {expected:08X})")]
+    PatchChecksum { expected: u32, actual: u32 },
+    #[error("Patch was made for a different ROM (its CRC32 is {actual:08X}, should be {expected:08X})")]
+    WrongSourceRom { expected: u32, actual: u32 },
+    #[error("Action at offset {0:#x} reads or writes out of bounds")]
+    InvalidAction(usize),
+    #[error("Patched ROM has size {actual:#x}, should be {expected:#x}")]
+    TargetSize { expected: usize, actual: usize },
+    #[error("Patched ROM would have size {0:#x}, which is more than a ROM can have")]
+    TargetTooLarge(usize),
+    #[error("Patched ROM is invalid (its CRC32 is {actual:08X}, should be {expected:08X})")]
+    TargetChecksum { expected: u32, actual: u32 },
+    #[error("Writing patched ROM:\n- {0}")]
+    Write(RomError),
+}
+
+#[derive(Debug, Error)]
+pub enum IpsError {
+    #[error("Missing \"PATCH\" header, this is not an IPS patch")]
+    MissingHeader,
+    #[error("Patch ends unexpectedly at offset {0:#x}")]
+    UnexpectedEnd(usize),
+    #[error("Unexpected data after the end of the patch at offset {0:#x}")]
+    TrailingData(usize),
+    #[error("Patched ROM size {0:#x} exceeds the 16 MiB limit of IPS")]
+    TooLarge(usize),
+    #[error("Patched file is only {0:#x} bytes long, shorter than its SMC header")]
+    TooSmall(usize),
+    #[error("Writing patched ROM:\n- {0}")]
+    Write(RomError),
 }
 
 #[derive(Debug, Error)]
@@ -197,6 +404,10 @@ pub enum RomParseError {
     SecondaryEntrance(usize, RomError),
     #[error("Could not parse color palettes:\n- {0}")]
     ColorPalettes(ColorPaletteParseError),
+    #[error("Could not parse Map16 tables:\n- {0}")]
+    Map16(Map16Error),
+    #[error("Could not parse GFX lists:\n- {0}")]
+    GfxSets(GfxSetError),
 }
 
 pub type ParseErr<'a> = nom::Err<nom::error::Error<&'a [u8]>>;
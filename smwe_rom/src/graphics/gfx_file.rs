@@ -2,13 +2,16 @@ use std::{
     convert::TryInto,
     fmt,
     fmt::{Display, Formatter},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
 };
 
 use nom::{bytes::complete::take, combinator::map_parser, multi::count, IResult};
 
 use crate::{
-    compression::lc_lz2,
-    error::{GfxFileParseError, ParseErr},
+    compression::{Codec, Decompressed},
+    error::{GfxExportError, GfxFileParseError, ParseErr},
     graphics::color::{Abgr1555, Rgba32},
     snes_utils::{addr::AddrSnes, rom::Rom, rom_slice::SnesSlice},
 };
@@ -16,6 +19,7 @@ use crate::{
 #[derive(Copy, Clone, Debug)]
 pub enum TileFormat {
     Tile2bpp,
+    Tile3bpp,
     Tile4bpp,
     Tile8bpp,
     TileMode7,
@@ -29,6 +33,7 @@ pub struct Tile {
 #[derive(Clone)]
 pub struct GfxFile {
     pub tile_format: TileFormat,
+    pub compression: Codec,
     pub tiles:       Vec<Tile>,
 }
 
@@ -39,6 +44,7 @@ impl Display for TileFormat {
         use TileFormat::*;
         f.write_str(match self {
             Tile2bpp => "2BPP",
+            Tile3bpp => "3BPP",
             Tile4bpp => "4BPP",
             Tile8bpp => "8BPP",
             TileMode7 => "Mode7",
@@ -51,6 +57,11 @@ impl Tile {
         Self::from_xbpp(input, 2)
     }
 
+    /// Same as 2bpp for the first two bitplanes, followed by the third bitplane with one byte per row.
+    pub fn from_3bpp(input: &[u8]) -> IResult<&[u8], Self> {
+        Self::from_xbpp(input, 3)
+    }
+
     pub fn from_4bpp(input: &[u8]) -> IResult<&[u8], Self> {
         Self::from_xbpp(input, 4)
     }
@@ -60,7 +71,7 @@ impl Tile {
     }
 
     fn from_xbpp(input: &[u8], x: usize) -> IResult<&[u8], Self> {
-        debug_assert!([2, 4, 8].contains(&x));
+        debug_assert!([2, 3, 4, 8].contains(&x));
         let (input, bytes) = take(x * 8)(input)?;
         let mut tile = Tile { color_indices: [0; N_PIXELS_IN_TILE].into() };
 
@@ -68,7 +79,11 @@ impl Tile {
             let (row, col) = (i / 8, 7 - (i % 8));
             let mut color_idx = 0;
             for bit_idx in 0..x {
-                let byte_idx = (2 * row) + (0x10 * (bit_idx / 2)) + (bit_idx % 2);
+                let byte_idx = if x == 3 && bit_idx == 2 {
+                    0x10 + row
+                } else {
+                    (2 * row) + (0x10 * (bit_idx / 2)) + (bit_idx % 2)
+                };
                 let color_idx_bit = if (bytes[byte_idx] & (1 << col)) > 0 { 1u8 } else { 0u8 };
                 color_idx |= color_idx_bit << bit_idx;
             }
@@ -84,6 +99,10 @@ impl Tile {
         Ok((input, tile))
     }
 
+    pub fn color_indices(&self) -> &[u8] {
+        &self.color_indices
+    }
+
     pub fn to_bgr555(&self, palette: &[Abgr1555]) -> Box<[Abgr1555]> {
         self.color_indices
             .iter()
@@ -98,91 +117,212 @@ impl Tile {
 }
 
 impl GfxFile {
+    /// Parses a GFX file, detecting whether it's compressed with LC_LZ2 or LC_LZ3.
     pub fn new(rom: &Rom, file_num: usize) -> Result<Self, GfxFileParseError> {
+        Self::parse(rom, file_num, None)
+    }
+
+    /// Parses a GFX file whose compression is already known, e.g. from the project's settings.
+    pub fn with_codec(rom: &Rom, file_num: usize, codec: Codec) -> Result<Self, GfxFileParseError> {
+        Self::parse(rom, file_num, Some(codec))
+    }
+
+    fn parse(rom: &Rom, file_num: usize, codec: Option<Codec>) -> Result<Self, GfxFileParseError> {
         debug_assert!(file_num < GFX_FILES_META.len());
 
         use TileFormat::*;
         type ParserFn = fn(&[u8]) -> IResult<&[u8], Tile>;
 
-        let (tile_format, slice) = GFX_FILES_META[file_num];
+        let (tile_format, vanilla_slice) = GFX_FILES_META[file_num];
         let (parser, tile_size_bytes): (ParserFn, usize) = match tile_format {
             Tile2bpp => (Tile::from_2bpp, 2 * 8),
+            Tile3bpp => (Tile::from_3bpp, 3 * 8),
             Tile4bpp => (Tile::from_4bpp, 4 * 8),
             Tile8bpp => (Tile::from_8bpp, 8 * 8),
             TileMode7 => (Tile::from_mode7, 8 * 8),
         };
 
-        let bytes = rom.slice_lorom(slice).map_err(GfxFileParseError::IsolatingData)?;
-        let decomp_bytes = lc_lz2::decompress(bytes).map_err(GfxFileParseError::DecompressingData)?;
-        assert_eq!(0, decomp_bytes.len() % tile_size_bytes);
+        let addr = Self::address(rom, file_num)?;
+        let bytes = rom.slice_snes(SnesSlice::new(addr, 0).infinite()).map_err(GfxFileParseError::IsolatingData)?;
+        let vanilla_size = Some(vanilla_slice.size).filter(|_| addr == vanilla_slice.begin);
+        let (compression, decompressed) = Self::decompress(bytes, tile_size_bytes, vanilla_size, codec)?;
+        if vanilla_size.is_some() && vanilla_size != Some(decompressed.input_len) {
+            log::info!(
+                "GFX file {:X} takes {} bytes instead of the original {}",
+                file_num,
+                decompressed.input_len,
+                vanilla_slice.size
+            );
+        }
+        let decomp_bytes = decompressed.output;
 
         let tile_count = decomp_bytes.len() / tile_size_bytes;
         let mut read_tiles = count(map_parser(take(tile_size_bytes), parser), tile_count);
 
         let (_, tiles) = read_tiles(&decomp_bytes).map_err(|_: ParseErr| GfxFileParseError::ParsingTile)?;
-        Ok(Self { tile_format, tiles })
+        Ok(Self { tile_format, compression, tiles })
+    }
+
+    /// Address the game loads the file from: the one in `GFX_POINTER_TABLES` for files 00-31, and
+    /// the original one for files 32 and 33.
+    pub fn address(rom: &Rom, file_num: usize) -> Result<AddrSnes, GfxFileParseError> {
+        if file_num < GFX_POINTER_COUNT {
+            let mut addr = 0;
+            for (shift, table) in GFX_POINTER_TABLES.iter().enumerate() {
+                let byte =
+                    rom.slice_snes(SnesSlice::new(*table + file_num, 1)).map_err(GfxFileParseError::ReadAddress)?;
+                addr |= (byte[0] as usize) << (8 * shift);
+            }
+            Ok(AddrSnes(addr))
+        } else {
+            Ok(GFX_FILES_META[file_num].1.begin)
+        }
+    }
+
+    /// Vanilla GFX files are compressed with LC_LZ2, but Lunar Magic can re-insert them with LC_LZ3.
+    /// The two formats share most commands, so when the codec isn't known, the data is decompressed
+    /// with both. A result only counts if it's made of whole tiles; if both are, the one that takes up
+    /// exactly the file's original compressed size wins, if the file wasn't moved, and LC_LZ2 otherwise.
+    fn decompress(
+        bytes: &[u8], tile_size_bytes: usize, compressed_size: Option<usize>, codec: Option<Codec>,
+    ) -> Result<(Codec, Decompressed), GfxFileParseError> {
+        let decompress_as = |codec: Codec| {
+            let decompressed = codec.decompress(bytes).map_err(GfxFileParseError::DecompressingData)?;
+            if decompressed.output.len() % tile_size_bytes != 0 {
+                Err(GfxFileParseError::DecompressedSize(decompressed.output.len(), tile_size_bytes))
+            } else {
+                Ok((codec, decompressed))
+            }
+        };
+
+        if let Some(codec) = codec {
+            return decompress_as(codec);
+        }
+        match (decompress_as(Codec::LcLz2), decompress_as(Codec::LcLz3)) {
+            (Ok(lz2), Ok(lz3))
+                if Some(lz3.1.input_len) == compressed_size && Some(lz2.1.input_len) != compressed_size =>
+            {
+                Ok(lz3)
+            }
+            (Ok(lz2), Ok(lz3)) => {
+                if lz2.1.output != lz3.1.output {
+                    log::warn!("GFX data decompresses both as LC_LZ2 and LC_LZ3, assuming LC_LZ2");
+                }
+                Ok(lz2)
+            }
+            (Ok(lz2), Err(_)) => Ok(lz2),
+            (Err(_), Ok(lz3)) => Ok(lz3),
+            (Err(lz2_err), Err(_)) => Err(lz2_err),
+        }
     }
 
     pub fn n_pixels(&self) -> usize {
         self.tiles.len() * N_PIXELS_IN_TILE
     }
+
+    /// Writes the file as an indexed PNG with `N_TILES_IN_PNG_ROW` tiles per row, keeping the colour
+    /// indices of the tiles as they are. The palette, e.g. a row of a level's colour palette, becomes
+    /// the PNG's PLTE; it's padded with magenta if the tiles use more colours than it has.
+    pub fn write_indexed_png<W: Write>(&self, writer: W, palette: &[Abgr1555]) -> Result<(), GfxExportError> {
+        let tile_rows = self.tiles.len() / N_TILES_IN_PNG_ROW + (self.tiles.len() % N_TILES_IN_PNG_ROW).min(1);
+        let (width, height) = (8 * N_TILES_IN_PNG_ROW, 8 * tile_rows.max(1));
+
+        let mut pixels = vec![0u8; width * height];
+        for (idx, tile) in self.tiles.iter().enumerate() {
+            let (x, y) = (8 * (idx % N_TILES_IN_PNG_ROW), 8 * (idx / N_TILES_IN_PNG_ROW));
+            for (row, indices) in tile.color_indices.chunks_exact(8).enumerate() {
+                let begin = (y + row) * width + x;
+                pixels[begin..begin + 8].copy_from_slice(indices);
+            }
+        }
+
+        let color_count = pixels.iter().copied().max().map_or(0, |max| max as usize + 1).max(palette.len()).max(1);
+        let colors = (0..color_count).map(|i| palette.get(i).copied().unwrap_or(Abgr1555::MAGENTA));
+        let (mut plte, mut trns) = (Vec::with_capacity(3 * color_count), Vec::with_capacity(color_count));
+        for color in colors.map(Rgba32::from) {
+            plte.extend([color.r, color.g, color.b].iter().map(|c| (c * 255.0).round() as u8));
+            trns.push((color.a * 255.0).round() as u8);
+        }
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(plte);
+        encoder.set_trns(trns);
+        let mut png_writer = encoder.write_header().map_err(GfxExportError::Encoding)?;
+        png_writer.write_image_data(&pixels).map_err(GfxExportError::Encoding)
+    }
+
+    pub fn export_indexed_png<P: AsRef<Path>>(&self, path: P, palette: &[Abgr1555]) -> Result<(), GfxExportError> {
+        let file = File::create(path).map_err(GfxExportError::Io)?;
+        self.write_indexed_png(BufWriter::new(file), palette)
+    }
 }
 
 // -------------------------------------------------------------------------------------------------
 
 pub const N_PIXELS_IN_TILE: usize = 8 * 8;
+pub const N_TILES_IN_PNG_ROW: usize = 16;
+
+/// Low, high and bank bytes of the addresses of GFX files 00-31, as read by the game's GFX loader.
+/// Files 32 and 33 are loaded from addresses hardcoded in the game's code.
+pub const GFX_POINTER_TABLES: [AddrSnes; 3] = [AddrSnes(0x00B992), AddrSnes(0x00B9C4), AddrSnes(0x00B9F6)];
+pub const GFX_POINTER_COUNT: usize = 0x32;
+
+/// Format of every file, with its location and compressed size in the original game. Hacks may move
+/// and resize files, so the sizes only help with picking the codec of files that weren't moved.
 #[rustfmt::skip]
-pub(crate) static GFX_FILES_META: [(TileFormat, SnesSlice); 0x34] = [
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x08D9F9), 2104)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x08E231), 2698)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x08ECBB), 2199)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x08F552), 2603)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x08FF7D), 2534)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x098963), 2569)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09936C), 2468)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x099D10), 2375)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09A657), 2378)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09AFA1), 2676)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09BA15), 2439)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09C39C), 2503)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09CD63), 2159)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09D5D2), 2041)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09DDCB), 2330)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09E6E5), 2105)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09EF1E), 2193)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09F7AF), 2062)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x09FFBD), 2387)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0A8910), 2616)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0A9348), 1952)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0A9AE8), 2188)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AA374), 1600)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AA9B4), 2297)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AB2AD), 2359)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0ABBE4), 1948)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AC380), 2278)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0ACC66), 2072)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AD47E), 2058)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0ADC88), 2551)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AE67F), 1988)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AEE43), 2142)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AF6A1), 2244)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0AFF65), 2408)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0B88CD), 2301)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0B91CA), 2331)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0B9AE5), 2256)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BA3B5), 2668)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BAE21), 2339)),
+pub static GFX_FILES_META: [(TileFormat, SnesSlice); 0x34] = [
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x08D9F9), 2104)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x08E231), 2698)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x08ECBB), 2199)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x08F552), 2603)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x08FF7D), 2534)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x098963), 2569)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09936C), 2468)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x099D10), 2375)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09A657), 2378)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09AFA1), 2676)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09BA15), 2439)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09C39C), 2503)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09CD63), 2159)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09D5D2), 2041)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09DDCB), 2330)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09E6E5), 2105)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09EF1E), 2193)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09F7AF), 2062)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x09FFBD), 2387)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0A8910), 2616)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0A9348), 1952)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0A9AE8), 2188)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AA374), 1600)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AA9B4), 2297)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AB2AD), 2359)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0ABBE4), 1948)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AC380), 2278)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0ACC66), 2072)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AD47E), 2058)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0ADC88), 2551)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AE67F), 1988)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AEE43), 2142)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AF6A1), 2244)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0AFF65), 2408)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0B88CD), 2301)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0B91CA), 2331)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0B9AE5), 2256)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BA3B5), 2668)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BAE21), 2339)),
     (TileFormat::TileMode7, SnesSlice::new(AddrSnes(0x0BB744), 2344)),
     (TileFormat::Tile2bpp,  SnesSlice::new(AddrSnes(0x0BC06C), 1591)),
     (TileFormat::Tile2bpp,  SnesSlice::new(AddrSnes(0x0BC6A3), 1240)),
     (TileFormat::Tile2bpp,  SnesSlice::new(AddrSnes(0x0BCB7B), 1397)),
     (TileFormat::Tile2bpp,  SnesSlice::new(AddrSnes(0x0BD0F0), 1737)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BD7B9), 2125)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BE006), 2352)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BE936), 2127)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BD7B9), 2125)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BE006), 2352)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BE936), 2127)),
     (TileFormat::Tile2bpp,  SnesSlice::new(AddrSnes(0x0BF185), 566)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BF3BB), 1093)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x0BF800), 1293)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BF3BB), 1093)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x0BF800), 1293)),
     (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x088000), 16320)),
-    (TileFormat::Tile4bpp,  SnesSlice::new(AddrSnes(0x08BFC0), 6713)),
+    (TileFormat::Tile3bpp,  SnesSlice::new(AddrSnes(0x08BFC0), 6713)),
 ];
@@ -0,0 +1,92 @@
+use nom::{bytes::complete::take, combinator::map, multi::count};
+
+use crate::{
+    error::GfxSetError,
+    graphics::gfx_file::GfxFile,
+    level::headers::PrimaryHeader,
+    snes_utils::{addr::AddrSnes, rom::Rom, rom_slice::SnesSlice},
+};
+
+/// VRAM slots that a level's tileset and sprite set choose the GFX files of, in the order they're
+/// laid out in VRAM.
+///
+/// Layer3 isn't listed: the game always fills its slots with GFX files 0x28-0x2B (status bar, message
+/// box font and Layer3 backgrounds), whatever the tileset. The FG4, BG2 and BG3 slots known from
+/// editors only exist in ROMs patched to load extra files, the original game never fills them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GfxSlot {
+    Sp1,
+    Sp2,
+    Sp3,
+    Sp4,
+    Fg1,
+    Fg2,
+    Bg1,
+    Fg3,
+}
+
+/// Numbers of the GFX files making up each tileset and sprite set.
+#[derive(Clone)]
+pub struct GfxSets {
+    /// FG1, FG2, BG1 and FG3 of every tileset, selected by `PrimaryHeader::fg_bg_gfx`.
+    pub tilesets:    Vec<[u8; 4]>,
+    /// SP1 to SP4 of every sprite set, selected by `PrimaryHeader::sprite_gfx`.
+    pub sprite_sets: Vec<[u8; 4]>,
+}
+
+pub const TILESET_COUNT: usize = 0x0F;
+pub const SPRITE_SET_COUNT: usize = 0x10;
+
+pub const TILESET_GFX_LISTS: SnesSlice = SnesSlice::new(AddrSnes(0x00A92B), 4 * TILESET_COUNT);
+pub const SPRITE_SET_GFX_LISTS: SnesSlice = SnesSlice::new(AddrSnes(0x00A8C3), 4 * SPRITE_SET_COUNT);
+
+// -------------------------------------------------------------------------------------------------
+
+impl GfxSlot {
+    pub const ALL: [GfxSlot; 8] = {
+        use GfxSlot::*;
+        [Sp1, Sp2, Sp3, Sp4, Fg1, Fg2, Bg1, Fg3]
+    };
+}
+
+impl GfxSets {
+    pub fn parse(rom: &Rom) -> Result<Self, GfxSetError> {
+        let parse_lists = |slice: SnesSlice, n: usize| {
+            let read_list = map(take(4usize), |list: &[u8]| [list[0], list[1], list[2], list[3]]);
+            rom.parse_slice_snes(slice, count(read_list, n)).map_err(|e| GfxSetError::Read(slice, e))
+        };
+        let tilesets = parse_lists(TILESET_GFX_LISTS, TILESET_COUNT)?;
+        let sprite_sets = parse_lists(SPRITE_SET_GFX_LISTS, SPRITE_SET_COUNT)?;
+        Ok(Self { tilesets, sprite_sets })
+    }
+
+    /// Number of the GFX file loaded into each slot for a level, in the order of `GfxSlot::ALL`.
+    pub fn level_file_nums(&self, header: &PrimaryHeader) -> Result<[(GfxSlot, usize); 8], GfxSetError> {
+        let tileset = self.tilesets.get(header.fg_bg_gfx() as usize).ok_or(GfxSetError::Tileset(header.fg_bg_gfx()))?;
+        let sprite_set =
+            self.sprite_sets.get(header.sprite_gfx() as usize).ok_or(GfxSetError::SpriteSet(header.sprite_gfx()))?;
+
+        let mut slots = [(GfxSlot::Sp1, 0); 8];
+        let file_nums = sprite_set.iter().chain(tileset.iter());
+        for ((slot, &file_num), &gfx_slot) in slots.iter_mut().zip(file_nums).zip(GfxSlot::ALL.iter()) {
+            *slot = (gfx_slot, file_num as usize);
+        }
+        Ok(slots)
+    }
+
+    /// GFX file loaded into each slot for a level, looked up in `gfx_files`, in the order of
+    /// `GfxSlot::ALL`.
+    pub fn level_slots<'a>(
+        &self, header: &PrimaryHeader, gfx_files: &'a [GfxFile],
+    ) -> Result<[(GfxSlot, &'a GfxFile); 8], GfxSetError> {
+        let file_nums = self.level_file_nums(header)?;
+        if let Some(&(_, file_num)) = file_nums.iter().find(|(_, file_num)| *file_num >= gfx_files.len()) {
+            return Err(GfxSetError::File(file_num));
+        }
+        let mut slots = [(GfxSlot::Sp1, &gfx_files[file_nums[0].1]); 8];
+        for (slot, &(gfx_slot, file_num)) in slots.iter_mut().zip(file_nums.iter()) {
+            *slot = (gfx_slot, &gfx_files[file_num]);
+        }
+        Ok(slots)
+    }
+}
@@ -0,0 +1,228 @@
+use std::ops::RangeInclusive;
+
+use nom::{combinator::map, multi::count, number::complete::le_u16, sequence::tuple, IResult};
+
+use crate::{
+    error::Map16Error,
+    graphics::gfx_set::TILESET_COUNT,
+    snes_utils::{addr::AddrSnes, rom::Rom, rom_slice::SnesSlice},
+};
+
+pub type Map16BlockId = u16;
+
+/// One of the four 8x8 tiles of a block, in the format of a SNES tilemap entry: `YXPCCCTT TTTTTTTT`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Map16Tile(pub u16);
+
+/// 16x16 block made of four 8x8 tiles.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Map16Block {
+    pub upper_left:  Map16Tile,
+    pub lower_left:  Map16Tile,
+    pub upper_right: Map16Tile,
+    pub lower_right: Map16Tile,
+}
+
+#[derive(Clone)]
+pub struct Map16Tables {
+    /// Blocks 000-1FF, which level objects are made of. Tileset-specific blocks hold the variant of
+    /// the first tileset, use `foreground_block` to get the one of a level's tileset.
+    pub foreground:     Vec<Map16Block>,
+    /// Tileset-specific blocks of every tileset, selected by `PrimaryHeader::fg_bg_gfx`, in the
+    /// order of `TILESET_SPECIFIC_BLOCKS`.
+    pub tileset_blocks: Vec<Vec<Map16Block>>,
+    /// Blocks of Layer2 backgrounds, indexed by their `BackgroundTileID` and the background's page.
+    pub background:     Vec<Map16Block>,
+}
+
+pub const MAP16_PAGE_SIZE: usize = 0x100;
+pub const MAP16_BLOCK_SIZE: usize = 4 * 2;
+
+pub const FOREGROUND_MAP16: SnesSlice = SnesSlice::new(AddrSnes(0x0D8000), 2 * MAP16_PAGE_SIZE * MAP16_BLOCK_SIZE);
+pub const BACKGROUND_MAP16: SnesSlice = SnesSlice::new(AddrSnes(0x0D9100), 2 * MAP16_PAGE_SIZE * MAP16_BLOCK_SIZE);
+
+/// Foreground blocks which look different in every tileset. Only the variant of the first tileset is
+/// stored with the other blocks.
+pub const TILESET_SPECIFIC_BLOCKS: [RangeInclusive<Map16BlockId>; 2] = [0x073..=0x0FF, 0x153..=0x16D];
+pub const TILESET_SPECIFIC_BLOCK_COUNT: usize = (0x0FF - 0x073 + 1) + (0x16D - 0x153 + 1);
+
+/// Bank $0D pointers to the tileset-specific blocks of every tileset. Tilesets may share blocks.
+pub const TILESET_MAP16_POINTERS: SnesSlice = SnesSlice::new(AddrSnes(0x0DA100), 2 * TILESET_COUNT);
+
+// -------------------------------------------------------------------------------------------------
+
+impl Map16Tile {
+    pub const PALETTE_MAX: u8 = 7;
+    pub const TILE_NUMBER_MAX: u16 = 0x3FF;
+
+    pub fn new(tile_number: u16, palette: u8, priority: bool, flip_x: bool, flip_y: bool) -> Result<Self, Map16Error> {
+        let mut tile = Self::default();
+        tile.set_tile_number(tile_number)?;
+        tile.set_palette(palette)?;
+        tile.set_priority(priority);
+        tile.set_flip_x(flip_x);
+        tile.set_flip_y(flip_y);
+        Ok(tile)
+    }
+
+    pub fn tile_number(self) -> u16 {
+        self.0 & Self::TILE_NUMBER_MAX
+    }
+
+    pub fn palette(self) -> u8 {
+        ((self.0 >> 10) & 0b111) as u8
+    }
+
+    pub fn priority(self) -> bool {
+        self.0 & (1 << 13) != 0
+    }
+
+    pub fn flip_x(self) -> bool {
+        self.0 & (1 << 14) != 0
+    }
+
+    pub fn flip_y(self) -> bool {
+        self.0 & (1 << 15) != 0
+    }
+
+    pub fn set_tile_number(&mut self, tile_number: u16) -> Result<(), Map16Error> {
+        check("tile_number", tile_number, Self::TILE_NUMBER_MAX)?;
+        self.0 = (self.0 & !Self::TILE_NUMBER_MAX) | tile_number;
+        Ok(())
+    }
+
+    pub fn set_palette(&mut self, palette: u8) -> Result<(), Map16Error> {
+        check("palette", palette as u16, Self::PALETTE_MAX as u16)?;
+        self.0 = (self.0 & !(0b111 << 10)) | ((palette as u16) << 10);
+        Ok(())
+    }
+
+    pub fn set_priority(&mut self, priority: bool) {
+        self.set_flag(13, priority);
+    }
+
+    pub fn set_flip_x(&mut self, flip_x: bool) {
+        self.set_flag(14, flip_x);
+    }
+
+    pub fn set_flip_y(&mut self, flip_y: bool) {
+        self.set_flag(15, flip_y);
+    }
+
+    fn set_flag(&mut self, bit: u16, value: bool) {
+        self.0 = (self.0 & !(1 << bit)) | ((value as u16) << bit);
+    }
+}
+
+impl Map16Block {
+    /// Tiles are stored column by column: upper left, lower left, upper right, lower right.
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let tile = || map(le_u16, Map16Tile);
+        map(tuple((tile(), tile(), tile(), tile())), |(upper_left, lower_left, upper_right, lower_right)| Self {
+            upper_left,
+            lower_left,
+            upper_right,
+            lower_right,
+        })(input)
+    }
+
+    pub fn tiles(&self) -> [Map16Tile; 4] {
+        [self.upper_left, self.lower_left, self.upper_right, self.lower_right]
+    }
+
+    pub fn to_bytes(&self) -> [u8; MAP16_BLOCK_SIZE] {
+        let mut bytes = [0; MAP16_BLOCK_SIZE];
+        for (chunk, tile) in bytes.chunks_exact_mut(2).zip(self.tiles().iter()) {
+            chunk.copy_from_slice(&tile.0.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+impl Map16Tables {
+    pub fn parse(rom: &Rom) -> Result<Self, Map16Error> {
+        let parse_blocks = |slice: SnesSlice| {
+            let block_count = slice.size / MAP16_BLOCK_SIZE;
+            rom.parse_slice_snes(slice, count(Map16Block::parse, block_count)).map_err(|e| Map16Error::Read(slice, e))
+        };
+        let foreground = parse_blocks(FOREGROUND_MAP16)?;
+        let tileset_blocks = Self::tileset_slices(rom)?.into_iter().map(parse_blocks).collect::<Result<Vec<_>, _>>()?;
+        let background = parse_blocks(BACKGROUND_MAP16)?;
+        Ok(Self { foreground, tileset_blocks, background })
+    }
+
+    /// Writes every table back to where it was read from. Tileset-specific blocks are written last,
+    /// and tilesets sharing their blocks must not have been edited differently.
+    pub fn write_to_rom(&self, rom: &mut Rom) -> Result<(), Map16Error> {
+        let tileset_slices = Self::tileset_slices(rom)?;
+        if self.tileset_blocks.len() != tileset_slices.len() {
+            return Err(Map16Error::TilesetCount(tileset_slices.len(), self.tileset_blocks.len()));
+        }
+        for (tileset, slice) in tileset_slices.iter().enumerate() {
+            let shared_with = tileset_slices[..tileset].iter().position(|other| other.begin == slice.begin);
+            if let Some(other) = shared_with {
+                if self.tileset_blocks[other] != self.tileset_blocks[tileset] {
+                    return Err(Map16Error::SharedTilesetBlocks(other, tileset));
+                }
+            }
+        }
+
+        let shared_tables = [(FOREGROUND_MAP16, &self.foreground), (BACKGROUND_MAP16, &self.background)];
+        let tables = shared_tables.iter().copied().chain(tileset_slices.into_iter().zip(self.tileset_blocks.iter()));
+        for (slice, blocks) in tables {
+            let expected = slice.size / MAP16_BLOCK_SIZE;
+            if blocks.len() != expected {
+                return Err(Map16Error::BlockCount(slice, expected, blocks.len()));
+            }
+            let bytes: Vec<u8> = blocks.iter().flat_map(|block| block.to_bytes().to_vec()).collect();
+            rom.write_snes(slice.begin, &bytes).map_err(|e| Map16Error::Write(slice, e))?;
+        }
+        Ok(())
+    }
+
+    /// Block as it looks in tileset `fg_bg_gfx`.
+    pub fn foreground_block(&self, block_id: Map16BlockId, fg_bg_gfx: u8) -> Option<&Map16Block> {
+        match Self::tileset_block_index(block_id) {
+            Some(index) => self.tileset_blocks.get(fg_bg_gfx as usize)?.get(index),
+            None => self.foreground.get(block_id as usize),
+        }
+    }
+
+    pub fn is_tileset_specific(block_id: Map16BlockId) -> bool {
+        TILESET_SPECIFIC_BLOCKS.iter().any(|range| range.contains(&block_id))
+    }
+
+    /// Index of a tileset-specific block within the blocks of a tileset.
+    pub fn tileset_block_index(block_id: Map16BlockId) -> Option<usize> {
+        let mut offset = 0;
+        for range in TILESET_SPECIFIC_BLOCKS.iter() {
+            if range.contains(&block_id) {
+                return Some(offset + (block_id - range.start()) as usize);
+            }
+            offset += range.clone().count();
+        }
+        None
+    }
+
+    fn tileset_slices(rom: &Rom) -> Result<Vec<SnesSlice>, Map16Error> {
+        let pointers = rom
+            .parse_slice_snes(TILESET_MAP16_POINTERS, count(le_u16, TILESET_COUNT))
+            .map_err(|e| Map16Error::Read(TILESET_MAP16_POINTERS, e))?;
+        Ok(pointers
+            .into_iter()
+            .map(|ptr| {
+                SnesSlice::new(AddrSnes(0x0D0000 | ptr as usize), TILESET_SPECIFIC_BLOCK_COUNT * MAP16_BLOCK_SIZE)
+            })
+            .collect())
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn check(field: &'static str, value: u16, max: u16) -> Result<(), Map16Error> {
+    if value > max {
+        Err(Map16Error::OutOfRange { field, value, max })
+    } else {
+        Ok(())
+    }
+}
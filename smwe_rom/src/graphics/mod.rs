@@ -1,3 +1,5 @@
 pub mod color;
 pub mod gfx_file;
+pub mod gfx_set;
+pub mod map16;
 pub mod palette;
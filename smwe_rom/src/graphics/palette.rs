@@ -154,7 +154,7 @@ fn make_color_parser(
 ) -> impl Fn(SnesSlice, ColorPaletteParseError) -> Result<Vec<Abgr1555>, ColorPaletteParseError> + '_ {
     move |slice, err| {
         let read_colors = many0(map(le_u16, Abgr1555));
-        rom.parse_slice_lorom(slice, read_colors).map_err(|_| err)
+        rom.parse_slice_snes(slice, read_colors).map_err(|_| err)
     }
 }
 
@@ -336,14 +336,14 @@ impl OverworldColorPaletteSet {
         }
 
         let indirect_table_1 = rom
-            .slice_lorom(LAYER2_PALETTE_INDIRECT1)
+            .slice_snes(LAYER2_PALETTE_INDIRECT1)
             .map_err(|_| ColorPaletteParseError::OverworldLayer2IndicesIndirect1Read(LAYER2_PALETTE_INDIRECT1))?;
 
         for &offset in indirect_table_1 {
             let index_offset = LAYER2_PALETTE_INDIRECT2.offset_forward(2 * offset as usize).begin;
             let ptr16_slice = SnesSlice::new(index_offset, 2);
             let ptr16 = rom
-                .parse_slice_lorom(ptr16_slice, le_u16)
+                .parse_slice_snes(ptr16_slice, le_u16)
                 .map_err(|_| ColorPaletteParseError::OverworldLayer2IndexRead(offset as usize))?;
 
             let idx = ptr16 / 0x38;
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use crate::{crc32::crc32, snes_utils::rom::Rom, RomInternalHeader};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SmwRevision {
+    NorthAmerica,
+    Japan,
+    Europe,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RomKind {
+    /// Unmodified dump of one of the game's releases.
+    Clean(SmwRevision),
+    /// SMW, recognised by its internal ROM name, but not one of `KNOWN_DUMPS`. Most likely a hack, but
+    /// it may also be a clean dump of a revision that isn't listed.
+    Unrecognised,
+    /// Neither a known dump of SMW nor a hack of it.
+    Unknown,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RomIdentity {
+    pub kind:           RomKind,
+    /// CRC32 of the ROM without its SMC header.
+    pub crc32:          u32,
+    pub has_smc_header: bool,
+}
+
+#[rustfmt::skip]
+static KNOWN_DUMPS: [(u32, SmwRevision); 3] = [
+    (0xB19ED489, SmwRevision::NorthAmerica),
+    (0x6B47BB75, SmwRevision::Japan),
+    (0xB47F5F20, SmwRevision::Europe),
+];
+
+const INTERNAL_ROM_NAME: &str = "SUPER MARIOWORLD";
+
+// -------------------------------------------------------------------------------------------------
+
+impl RomIdentity {
+    pub fn identify(rom: &Rom) -> Self {
+        let crc32 = crc32(rom.as_bytes());
+        let kind = if let Some(&(_, revision)) = KNOWN_DUMPS.iter().find(|(known, _)| *known == crc32) {
+            RomKind::Clean(revision)
+        } else if matches!(RomInternalHeader::parse(rom), Ok(header) if header.internal_rom_name.trim_end() == INTERNAL_ROM_NAME)
+        {
+            RomKind::Unrecognised
+        } else {
+            RomKind::Unknown
+        };
+        log::info!("Identified ROM as: {} (CRC32: {:08X})", kind, crc32);
+        Self { kind, crc32, has_smc_header: rom.has_smc_header() }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        matches!(self.kind, RomKind::Clean(_))
+    }
+}
+
+impl fmt::Display for SmwRevision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SmwRevision::NorthAmerica => "North America 1.0",
+            SmwRevision::Japan => "Japan",
+            SmwRevision::Europe => "Europe",
+        })
+    }
+}
+
+impl fmt::Display for RomKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomKind::Clean(revision) => write!(f, "Clean SMW ({})", revision),
+            RomKind::Unrecognised => f.write_str("SMW, but not a recognised clean dump"),
+            RomKind::Unknown => f.write_str("Unknown ROM"),
+        }
+    }
+}
+
+impl fmt::Display for RomIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}", self.kind, if self.has_smc_header { "headered" } else { "unheadered" })
+    }
+}
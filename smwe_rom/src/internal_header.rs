@@ -9,12 +9,14 @@ use nom::{
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{
-    error::InternalHeaderParseError,
+    error::{InternalHeaderParseError, RomError},
     snes_utils::{addr::AddrPc, rom::Rom, rom_slice::PcSlice},
 };
 
 #[rustfmt::skip]
 pub mod offsets {
+    pub const MAP_MODE:         usize = 0x15;
+    pub const ROM_SIZE:         usize = 0x17;
     pub const COMPLEMENT_CHECK: usize = 0x1C;
     pub const CHECKSUM:         usize = 0x1E;
 }
@@ -28,6 +30,7 @@ pub mod sizes {
 // -------------------------------------------------------------------------------------------------
 
 pub struct RomInternalHeader {
+    pub location:          PcSlice,
     pub internal_rom_name: String,
     pub map_mode:          MapMode,
     pub rom_type:          RomType,
@@ -36,6 +39,10 @@ pub struct RomInternalHeader {
     pub region_code:       RegionCode,
     pub developer_id:      u8,
     pub version_number:    u8,
+    pub complement_check:  u16,
+    pub checksum:          u16,
+    /// Checksum calculated from the contents of the ROM at the time of parsing.
+    pub actual_checksum:   u16,
 }
 
 #[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive)]
@@ -44,11 +51,12 @@ pub enum MapMode {
     SlowLoRom   = 0b100000,
     SlowHiRom   = 0b100001,
     SlowExLoRom = 0b100010,
-    SlowExHiRom = 0b100100,
+    SlowExHiRom = 0b100101,
     FastLoRom   = 0b110000,
     FastHiRom   = 0b110001,
     FastExLoRom = 0b110010,
-    FastExHiRom = 0b110100,
+    FastExHiRom = 0b110101,
+    Sa1         = 0b100011,
 }
 
 #[derive(Copy, Clone, IntoPrimitive, TryFromPrimitive)]
@@ -135,37 +143,56 @@ impl RomInternalHeader {
         let read_rom_type = map_res(le_u8, RomType::try_from);
         let read_region_code = map_res(le_u8, RegionCode::try_from);
 
+        let (complement_check, checksum) = rom
+            .parse_slice_pc(rih_slice.offset_forward(offsets::COMPLEMENT_CHECK).resize(4), pair(le_u16, le_u16))
+            .map_err(InternalHeaderParseError::ReadChecksum)?;
+
         Ok(Self {
+            location: rih_slice,
             internal_rom_name: rom
                 .parse_slice_pc(irn_slice, read_internal_rom_name)
                 .map_err(InternalHeaderParseError::ReadRomName)?,
-            map_mode:          rom
-                .parse_slice_pc(byte_slice, read_map_mode)
-                .map_err(InternalHeaderParseError::ReadMapMode)?,
-            rom_type:          rom
+            map_mode: rom.parse_slice_pc(byte_slice, read_map_mode).map_err(InternalHeaderParseError::ReadMapMode)?,
+            rom_type: rom
                 .parse_slice_pc(byte_slice.skip_forward(1), read_rom_type)
                 .map_err(InternalHeaderParseError::ReadRomType)?,
-            rom_size:          rom
+            rom_size: rom
                 .parse_slice_pc(byte_slice.skip_forward(2), le_u8)
                 .map_err(InternalHeaderParseError::ReadRomSize)?,
-            sram_size:         rom
+            sram_size: rom
                 .parse_slice_pc(byte_slice.skip_forward(3), le_u8)
                 .map_err(InternalHeaderParseError::ReadSramSize)?,
-            region_code:       rom
+            region_code: rom
                 .parse_slice_pc(byte_slice.skip_forward(4), read_region_code)
                 .map_err(InternalHeaderParseError::ReadRegionCode)?,
-            developer_id:      rom
+            developer_id: rom
                 .parse_slice_pc(byte_slice.skip_forward(5), le_u8)
                 .map_err(InternalHeaderParseError::ReadDeveloperId)?,
-            version_number:    rom
+            version_number: rom
                 .parse_slice_pc(byte_slice.skip_forward(6), le_u8)
                 .map_err(InternalHeaderParseError::ReadVersionNumber)?,
+            complement_check,
+            checksum,
+            actual_checksum: Self::calculate_checksum(rom, rih_slice),
         })
     }
 
     fn find(rom: &Rom) -> Result<PcSlice, InternalHeaderParseError> {
         const HEADER_LOROM: PcSlice = PcSlice::new(AddrPc(0x007FC0), 64);
         const HEADER_HIROM: PcSlice = PcSlice::new(AddrPc(0x00FFC0), 64);
+        const HEADER_EXHIROM: PcSlice = PcSlice::new(AddrPc(0x40FFC0), 64);
+
+        // Only ExHiROM images are big enough to have a header there, so it's not an error if it's missing.
+        let exhi_cpl_csm = HEADER_EXHIROM.offset_forward(offsets::COMPLEMENT_CHECK).resize(4);
+        let exhi_map_mode = HEADER_EXHIROM.offset_forward(offsets::MAP_MODE).resize(1);
+        let exhi_checksum = rom.parse_slice_pc(exhi_cpl_csm, pair(le_u16, le_u16));
+        let exhi_mode = rom.parse_slice_pc(exhi_map_mode, map_res(le_u8, MapMode::try_from));
+        let exhi_valid = matches!(exhi_checksum, Ok((cpl, csm)) if (cpl ^ csm) == 0xFFFF)
+            && matches!(exhi_mode, Ok(mode) if mode.is_exhirom());
+        if exhi_valid {
+            log::info!("Internal ROM header found at ExHiROM location: {:#X}", HEADER_EXHIROM.begin);
+            return Ok(HEADER_EXHIROM);
+        }
 
         let lo_cpl_csm = HEADER_LOROM.offset_forward(offsets::COMPLEMENT_CHECK).resize(4);
         let hi_cpl_csm = HEADER_HIROM.offset_forward(offsets::COMPLEMENT_CHECK).resize(4);
@@ -190,6 +217,57 @@ impl RomInternalHeader {
         }
     }
 
+    /// Sums all bytes of the ROM, as if the checksum and its complement were 0x0000 and 0xFFFF.
+    /// If the ROM size is not a power of two, the part past the largest power of two is mirrored
+    /// until it's as big as that power of two, the same way the SNES maps it.
+    pub fn calculate_checksum(rom: &Rom, header: PcSlice) -> u16 {
+        fn mirrored_sum(data: &[u8], mut mask: usize) -> u32 {
+            while (data.len() & mask) == 0 {
+                mask >>= 1;
+            }
+            let mut sum = data[..mask].iter().map(|&b| b as u32).fold(0u32, u32::wrapping_add);
+            let mut remainder = data.len() - mask;
+            if remainder > 0 {
+                let mut subsum = mirrored_sum(&data[mask..], mask >> 1);
+                while remainder < mask {
+                    remainder += remainder;
+                    subsum = subsum.wrapping_add(subsum);
+                }
+                sum = sum.wrapping_add(subsum);
+            }
+            sum
+        }
+
+        let data = rom.as_bytes();
+        if data.is_empty() {
+            return 0;
+        }
+        let mask = (data.len() + 1).next_power_of_two() / 2;
+        let cpl_csm = header.begin.0 + offsets::COMPLEMENT_CHECK;
+        let stored: u32 = data.get(cpl_csm..cpl_csm + 4).map_or(0, |b| b.iter().map(|&b| b as u32).sum());
+        let sum = mirrored_sum(data, mask).wrapping_sub(stored).wrapping_add(0xFF + 0xFF);
+        sum as u16
+    }
+
+    pub fn checksum_valid(&self) -> bool {
+        self.checksum == self.actual_checksum && (self.checksum ^ self.complement_check) == 0xFFFF
+    }
+
+    /// Recalculates the checksum from the current contents of the ROM, and writes it and its complement
+    /// into the internal header.
+    pub fn update_checksum(&mut self, rom: &mut Rom) -> Result<(), RomError> {
+        let checksum = Self::calculate_checksum(rom, self.location);
+        let complement_check = !checksum;
+        let cpl_csm = self.location.begin + offsets::COMPLEMENT_CHECK;
+        let [cpl_lo, cpl_hi] = complement_check.to_le_bytes();
+        let [csm_lo, csm_hi] = checksum.to_le_bytes();
+        rom.write_pc(cpl_csm, &[cpl_lo, cpl_hi, csm_lo, csm_hi])?;
+        self.complement_check = complement_check;
+        self.checksum = checksum;
+        self.actual_checksum = checksum;
+        Ok(())
+    }
+
     pub fn rom_size_in_kb(&self) -> u32 {
         let exponent = self.rom_size as u32;
         2u32.pow(exponent)
@@ -215,6 +293,7 @@ impl fmt::Display for MapMode {
             FastHiRom => "Fast HiROM",
             FastExLoRom => "Fast ExLoROM",
             FastExHiRom => "Fast ExHiROM",
+            Sa1 => "SA-1",
         })
     }
 }
@@ -224,10 +303,18 @@ impl MapMode {
     pub fn as_u8(&self) -> u8 { (*self).into() }
     pub fn is_slow(&self)    -> bool { (self.as_u8() & 0b010000) == 0 }
     pub fn is_fast(&self)    -> bool { !self.is_slow() }
-    pub fn is_lorom(&self)   -> bool { (self.as_u8() & 0b000001) == 0 }
-    pub fn is_hirom(&self)   -> bool { (self.as_u8() & 0b000001) != 0 }
-    pub fn is_exlorom(&self) -> bool { (self.as_u8() & 0b000010) != 0 }
-    pub fn is_exhirom(&self) -> bool { (self.as_u8() & 0b000100) != 0 }
+    pub fn is_lorom(&self)   -> bool { matches!(self, MapMode::SlowLoRom | MapMode::FastLoRom) }
+    pub fn is_hirom(&self)   -> bool { matches!(self, MapMode::SlowHiRom | MapMode::FastHiRom) }
+    pub fn is_exlorom(&self) -> bool { matches!(self, MapMode::SlowExLoRom | MapMode::FastExLoRom) }
+    pub fn is_exhirom(&self) -> bool { matches!(self, MapMode::SlowExHiRom | MapMode::FastExHiRom) }
+    pub fn is_sa1(&self)     -> bool { matches!(self, MapMode::Sa1) }
+}
+
+impl RomType {
+    pub fn has_sa1(&self) -> bool {
+        let self_as_byte: u8 = (*self).into();
+        (self_as_byte & 0xF0) == 0x30 && (self_as_byte & 0x0F) >= 0x3
+    }
 }
 
 impl fmt::Display for RomType {
@@ -2,14 +2,18 @@ use crate::{compression::lc_rle1, error::DecompressionError};
 
 pub type BackgroundTileID = u8;
 
-#[derive(Clone)]
+#[derive(Clone, Eq, PartialEq)]
 pub struct BackgroundData {
     tile_ids: Vec<BackgroundTileID>,
 }
 
 impl BackgroundData {
     pub fn read_from(input: &[u8]) -> Result<Self, DecompressionError> {
-        let tile_ids = lc_rle1::decompress(input)?;
+        let tile_ids = lc_rle1::decompress(input)?.output;
         Ok(Self { tile_ids })
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        lc_rle1::compress(&self.tile_ids)
+    }
 }
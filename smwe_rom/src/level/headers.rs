@@ -1,9 +1,10 @@
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
 use nom::{bytes::complete::take, IResult};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{
-    error::RomError,
+    error::{LevelHeaderError, RomError},
     snes_utils::{addr::AddrSnes, rom::Rom, rom_slice::SnesSlice},
 };
 
@@ -11,6 +12,9 @@ pub const PRIMARY_HEADER_SIZE: usize = 5;
 pub const SECONDARY_HEADER_SIZE: usize = 4;
 pub const SPRITE_HEADER_SIZE: usize = 1;
 
+const SECONDARY_HEADER_TABLES: [AddrSnes; SECONDARY_HEADER_SIZE] =
+    [AddrSnes(0x05F000), AddrSnes(0x05F200), AddrSnes(0x05F400), AddrSnes(0x05F600)];
+
 #[derive(Clone)]
 pub struct PrimaryHeader([u8; PRIMARY_HEADER_SIZE]);
 
@@ -20,33 +24,158 @@ pub struct SecondaryHeader([u8; SECONDARY_HEADER_SIZE]);
 #[derive(Clone)]
 pub struct SpriteHeader(u8);
 
+/// Level modes with a name. Every other mode, including some the original game uses (e.g. boss
+/// arenas), is kept as it is in `Other`. Named modes always convert to their own variant, so two
+/// `LevelMode`s are equal exactly when their values are.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LevelMode {
+    Horizontal,
+    HorizontalLayer2,
+    Vertical,
+    VerticalLayer2,
+    HorizontalDarkBackground,
+    HorizontalTranslucent,
+    HorizontalTranslucentLayer2,
+    Other(OtherLevelMode),
+}
+
+/// Value of a level mode without a named `LevelMode` variant. Only `LevelMode::from` creates these.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct OtherLevelMode(u8);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum Music {
+    Overworld    = 0,
+    Athletic     = 1,
+    Underground  = 2,
+    Water        = 3,
+    Castle       = 4,
+    GhostHouse   = 5,
+    Bonus        = 6,
+    SwitchPalace = 7,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum Timer {
+    Disabled   = 0,
+    Seconds200 = 1,
+    Seconds300 = 2,
+    Seconds400 = 3,
+}
+
+/// Which of the tables keeping track of collected items the level uses.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum ItemMemory {
+    Table0   = 0,
+    Table1   = 1,
+    Table2   = 2,
+    Disabled = 3,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum VerticalScroll {
+    AtWill                      = 0,
+    Disabled                    = 1,
+    Always                      = 2,
+    VerticalAndHorizontalLocked = 3,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PrimaryHeaderBuilder {
+    pub palette_bg:      u8,
+    pub level_length:    u8,
+    pub back_area_color: u8,
+    pub level_mode:      LevelMode,
+    pub layer3_priority: bool,
+    pub music:           Music,
+    pub sprite_gfx:      u8,
+    pub timer:           Timer,
+    pub palette_sprite:  u8,
+    pub palette_fg:      u8,
+    pub item_memory:     ItemMemory,
+    pub vertical_scroll: VerticalScroll,
+    pub fg_bg_gfx:       u8,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SecondaryHeaderBuilder {
+    pub layer2_scroll:              u8,
+    pub main_entrance_xy_pos:       (u8, u8),
+    pub layer3:                     u8,
+    pub main_entrance_mario_action: u8,
+    pub midway_entrance_screen:     u8,
+    pub fg_initial_pos:             u8,
+    pub bg_initial_pos:             u8,
+    pub no_yoshi_level:             bool,
+    pub unknown_vertical_pos_level: bool,
+    pub vertical_level:             bool,
+    pub main_entrance_screen:       u8,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SpriteHeaderBuilder {
+    pub sprite_buoyancy:            bool,
+    pub disable_layer2_interaction: bool,
+    pub sprite_memory:              u8,
+}
+
+// -------------------------------------------------------------------------------------------------
+
 impl PrimaryHeader {
     pub fn new(bytes: &[u8]) -> Self {
         Self(bytes.try_into().unwrap())
     }
 
+    pub fn to_bytes(&self) -> [u8; PRIMARY_HEADER_SIZE] {
+        self.0
+    }
+
     pub fn palette_bg(&self) -> u8 {
         // BBB----- -------- -------- -------- --------
         // palette_bg = BBB
         self.0[0] >> 5
     }
 
+    pub fn set_palette_bg(&mut self, palette_bg: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[0], 5, 0b111, check("palette_bg", palette_bg, 0b111)?);
+        Ok(())
+    }
+
     pub fn level_length(&self) -> u8 {
         // ---LLLLL -------- -------- -------- --------
         // level_length = LLLLL
         self.0[0] & 0b11111
     }
 
+    pub fn set_level_length(&mut self, level_length: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[0], 0, 0b11111, check("level_length", level_length, 0b11111)?);
+        Ok(())
+    }
+
     pub fn back_area_color(&self) -> u8 {
         // -------- CCC----- -------- -------- --------
         // back_area_color = CCC
         self.0[1] >> 5
     }
 
-    pub fn level_mode(&self) -> u8 {
+    pub fn set_back_area_color(&mut self, back_area_color: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[1], 5, 0b111, check("back_area_color", back_area_color, 0b111)?);
+        Ok(())
+    }
+
+    pub fn level_mode(&self) -> LevelMode {
         // -------- ---MMMMM -------- -------- --------
         // level_mode = MMMMM
-        self.0[1] & 0b11111
+        LevelMode::from(self.0[1] & 0b11111)
+    }
+
+    pub fn set_level_mode(&mut self, level_mode: LevelMode) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[1], 0, 0b11111, check("level_mode", level_mode.into(), 0b11111)?);
+        Ok(())
     }
 
     pub fn layer3_priority(&self) -> bool {
@@ -55,10 +184,18 @@ impl PrimaryHeader {
         (self.0[2] >> 7) != 0
     }
 
-    pub fn music(&self) -> u8 {
+    pub fn set_layer3_priority(&mut self, layer3_priority: bool) {
+        set_bits(&mut self.0[2], 7, 0b1, layer3_priority as u8);
+    }
+
+    pub fn music(&self) -> Music {
         // -------- -------- -MMM---- -------- --------
         // music = MMM
-        (self.0[2] >> 4) & 0b111
+        Music::try_from((self.0[2] >> 4) & 0b111).unwrap()
+    }
+
+    pub fn set_music(&mut self, music: Music) {
+        set_bits(&mut self.0[2], 4, 0b111, music.into());
     }
 
     pub fn sprite_gfx(&self) -> u8 {
@@ -67,10 +204,19 @@ impl PrimaryHeader {
         self.0[2] & 0b1111
     }
 
-    pub fn timer(&self) -> u8 {
+    pub fn set_sprite_gfx(&mut self, sprite_gfx: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[2], 0, 0b1111, check("sprite_gfx", sprite_gfx, 0b1111)?);
+        Ok(())
+    }
+
+    pub fn timer(&self) -> Timer {
         // -------- -------- -------- TT------ --------
         // timer = TT
-        self.0[3] >> 6
+        Timer::try_from(self.0[3] >> 6).unwrap()
+    }
+
+    pub fn set_timer(&mut self, timer: Timer) {
+        set_bits(&mut self.0[3], 6, 0b11, timer.into());
     }
 
     pub fn palette_sprite(&self) -> u8 {
@@ -79,22 +225,40 @@ impl PrimaryHeader {
         (self.0[3] >> 3) & 0b111
     }
 
+    pub fn set_palette_sprite(&mut self, palette_sprite: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[3], 3, 0b111, check("palette_sprite", palette_sprite, 0b111)?);
+        Ok(())
+    }
+
     pub fn palette_fg(&self) -> u8 {
         // -------- -------- -------- -----FFF --------
         // palette_fg = FFF
         self.0[3] & 0b111
     }
 
-    pub fn item_memory(&self) -> u8 {
+    pub fn set_palette_fg(&mut self, palette_fg: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[3], 0, 0b111, check("palette_fg", palette_fg, 0b111)?);
+        Ok(())
+    }
+
+    pub fn item_memory(&self) -> ItemMemory {
         // -------- -------- -------- -------- II------
         // item_memory = II
-        self.0[4] >> 6
+        ItemMemory::try_from(self.0[4] >> 6).unwrap()
+    }
+
+    pub fn set_item_memory(&mut self, item_memory: ItemMemory) {
+        set_bits(&mut self.0[4], 6, 0b11, item_memory.into());
     }
 
-    pub fn vertical_scroll(&self) -> u8 {
+    pub fn vertical_scroll(&self) -> VerticalScroll {
         // -------- -------- -------- -------- --VV----
         // vertical_scroll = VV
-        (self.0[4] >> 4) & 0b11
+        VerticalScroll::try_from((self.0[4] >> 4) & 0b11).unwrap()
+    }
+
+    pub fn set_vertical_scroll(&mut self, vertical_scroll: VerticalScroll) {
+        set_bits(&mut self.0[4], 4, 0b11, vertical_scroll.into());
     }
 
     pub fn fg_bg_gfx(&self) -> u8 {
@@ -102,16 +266,33 @@ impl PrimaryHeader {
         // fg_bg_gfx = GGGG
         self.0[4] & 0b1111
     }
+
+    pub fn set_fg_bg_gfx(&mut self, fg_bg_gfx: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[4], 0, 0b1111, check("fg_bg_gfx", fg_bg_gfx, 0b1111)?);
+        Ok(())
+    }
 }
 
 impl SecondaryHeader {
     pub fn read_from_rom(rom: &Rom, level_num: usize) -> Result<Self, RomError> {
         let take_byte = |addr| {
-            let slice = SnesSlice::new(AddrSnes(addr), 0x200);
-            let byte_table = rom.slice_lorom(slice)?;
+            let slice = SnesSlice::new(addr, 0x200);
+            let byte_table = rom.slice_snes(slice)?;
             Ok(byte_table[level_num])
         };
-        Ok(Self([take_byte(0x05F000)?, take_byte(0x05F200)?, take_byte(0x05F400)?, take_byte(0x05F600)?]))
+        Ok(Self([
+            take_byte(SECONDARY_HEADER_TABLES[0])?,
+            take_byte(SECONDARY_HEADER_TABLES[1])?,
+            take_byte(SECONDARY_HEADER_TABLES[2])?,
+            take_byte(SECONDARY_HEADER_TABLES[3])?,
+        ]))
+    }
+
+    pub fn write_to_rom(&self, rom: &mut Rom, level_num: usize) -> Result<(), RomError> {
+        for (table, &byte) in SECONDARY_HEADER_TABLES.iter().zip(self.0.iter()) {
+            rom.write_snes(*table + level_num, &[byte])?;
+        }
+        Ok(())
     }
 
     pub fn layer2_scroll(&self) -> u8 {
@@ -120,6 +301,11 @@ impl SecondaryHeader {
         (self.0[0] >> 4) & 0b1111
     }
 
+    pub fn set_layer2_scroll(&mut self, layer2_scroll: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[0], 4, 0b1111, check("layer2_scroll", layer2_scroll, 0b1111)?);
+        Ok(())
+    }
+
     pub fn main_entrance_xy_pos(&self) -> (u8, u8) {
         // ----YYYY -----XXX -------- --------
         // main_entrance_xy_pos = (XXX, YYYY)
@@ -128,59 +314,109 @@ impl SecondaryHeader {
         (x, y)
     }
 
+    pub fn set_main_entrance_xy_pos(&mut self, (x, y): (u8, u8)) -> Result<(), LevelHeaderError> {
+        let x = check("main_entrance_x_pos", x, 0b111)?;
+        let y = check("main_entrance_y_pos", y, 0b1111)?;
+        set_bits(&mut self.0[1], 0, 0b111, x);
+        set_bits(&mut self.0[0], 0, 0b1111, y);
+        Ok(())
+    }
+
     pub fn layer3(&self) -> u8 {
         // -------- LL------ -------- --------
         // layer3 = LL
         (self.0[1] >> 6) & 0b11
     }
 
+    pub fn set_layer3(&mut self, layer3: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[1], 6, 0b11, check("layer3", layer3, 0b11)?);
+        Ok(())
+    }
+
     pub fn main_entrance_mario_action(&self) -> u8 {
         // -------- --AAA--- -------- --------
         // main_entrance_mario_action = AAA
         (self.0[1] >> 3) & 0b111
     }
 
+    pub fn set_main_entrance_mario_action(&mut self, action: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[1], 3, 0b111, check("main_entrance_mario_action", action, 0b111)?);
+        Ok(())
+    }
+
     pub fn midway_entrance_screen(&self) -> u8 {
         // -------- -------- SSSS---- --------
         // midway_entrance_screen = SSSS
         (self.0[2] >> 4) & 0b1111
     }
 
+    pub fn set_midway_entrance_screen(&mut self, screen: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[2], 4, 0b1111, check("midway_entrance_screen", screen, 0b1111)?);
+        Ok(())
+    }
+
     pub fn fg_initial_pos(&self) -> u8 {
         // -------- -------- ----FF-- --------
         // fg_initial_pos = FF
         (self.0[2] >> 2) & 0b11
     }
 
+    pub fn set_fg_initial_pos(&mut self, fg_initial_pos: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[2], 2, 0b11, check("fg_initial_pos", fg_initial_pos, 0b11)?);
+        Ok(())
+    }
+
     pub fn bg_initial_pos(&self) -> u8 {
         // -------- -------- ------BB --------
         // bg_initial_pos = BB
         self.0[2] & 0b11
     }
 
+    pub fn set_bg_initial_pos(&mut self, bg_initial_pos: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[2], 0, 0b11, check("bg_initial_pos", bg_initial_pos, 0b11)?);
+        Ok(())
+    }
+
     pub fn no_yoshi_level(&self) -> bool {
         // -------- -------- -------- Y-------
         // no_yoshi_level = Y
         (self.0[3] >> 7) != 0
     }
 
+    pub fn set_no_yoshi_level(&mut self, no_yoshi_level: bool) {
+        set_bits(&mut self.0[3], 7, 0b1, no_yoshi_level as u8);
+    }
+
     pub fn unknown_vertical_pos_level(&self) -> bool {
         // -------- -------- -------- -U------
         // unknown_vertical_pos_level = U
         (self.0[3] & 0b01000000) != 0
     }
 
+    pub fn set_unknown_vertical_pos_level(&mut self, unknown_vertical_pos_level: bool) {
+        set_bits(&mut self.0[3], 6, 0b1, unknown_vertical_pos_level as u8);
+    }
+
     pub fn vertical_level(&self) -> bool {
         // -------- -------- -------- --V-----
         // vertical_level = V
         (self.0[3] & 0b00100000) != 0
     }
 
+    pub fn set_vertical_level(&mut self, vertical_level: bool) {
+        set_bits(&mut self.0[3], 5, 0b1, vertical_level as u8);
+    }
+
     pub fn main_entrance_screen(&self) -> u8 {
         // -------- -------- -------- ---EEEEE
         // main_entrance_screen = EEEEE
         self.0[3] & 0b11111
     }
+
+    pub fn set_main_entrance_screen(&mut self, screen: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0[3], 0, 0b11111, check("main_entrance_screen", screen, 0b11111)?);
+        Ok(())
+    }
 }
 
 impl SpriteHeader {
@@ -189,21 +425,182 @@ impl SpriteHeader {
         Ok((input, Self(bytes[0])))
     }
 
+    pub fn to_byte(&self) -> u8 {
+        self.0
+    }
+
     pub fn sprite_buoyancy(&self) -> bool {
         // B-------
         // sprite_buoyancy = B
         (self.0 & 0b10000000) != 0
     }
 
+    pub fn set_sprite_buoyancy(&mut self, sprite_buoyancy: bool) {
+        set_bits(&mut self.0, 7, 0b1, sprite_buoyancy as u8);
+    }
+
     pub fn disable_layer2_interaction(&self) -> bool {
         // -L------
         // disable_layer2_interaction = L
         (self.0 & 0b01000000) != 0
     }
 
+    pub fn set_disable_layer2_interaction(&mut self, disable_layer2_interaction: bool) {
+        set_bits(&mut self.0, 6, 0b1, disable_layer2_interaction as u8);
+    }
+
     pub fn sprite_memory(&self) -> u8 {
         // --MMMMMM
         // sprite_memory = MMMMMM
         self.0 & 0b00111111
     }
+
+    pub fn set_sprite_memory(&mut self, sprite_memory: u8) -> Result<(), LevelHeaderError> {
+        set_bits(&mut self.0, 0, 0b111111, check("sprite_memory", sprite_memory, 0b111111)?);
+        Ok(())
+    }
+}
+
+impl PrimaryHeaderBuilder {
+    pub fn build(&self) -> Result<PrimaryHeader, LevelHeaderError> {
+        let mut header = PrimaryHeader([0; PRIMARY_HEADER_SIZE]);
+        header.set_palette_bg(self.palette_bg)?;
+        header.set_level_length(self.level_length)?;
+        header.set_back_area_color(self.back_area_color)?;
+        header.set_level_mode(self.level_mode)?;
+        header.set_layer3_priority(self.layer3_priority);
+        header.set_music(self.music);
+        header.set_sprite_gfx(self.sprite_gfx)?;
+        header.set_timer(self.timer);
+        header.set_palette_sprite(self.palette_sprite)?;
+        header.set_palette_fg(self.palette_fg)?;
+        header.set_item_memory(self.item_memory);
+        header.set_vertical_scroll(self.vertical_scroll);
+        header.set_fg_bg_gfx(self.fg_bg_gfx)?;
+        Ok(header)
+    }
+}
+
+impl SecondaryHeaderBuilder {
+    pub fn build(&self) -> Result<SecondaryHeader, LevelHeaderError> {
+        let mut header = SecondaryHeader([0; SECONDARY_HEADER_SIZE]);
+        header.set_layer2_scroll(self.layer2_scroll)?;
+        header.set_main_entrance_xy_pos(self.main_entrance_xy_pos)?;
+        header.set_layer3(self.layer3)?;
+        header.set_main_entrance_mario_action(self.main_entrance_mario_action)?;
+        header.set_midway_entrance_screen(self.midway_entrance_screen)?;
+        header.set_fg_initial_pos(self.fg_initial_pos)?;
+        header.set_bg_initial_pos(self.bg_initial_pos)?;
+        header.set_no_yoshi_level(self.no_yoshi_level);
+        header.set_unknown_vertical_pos_level(self.unknown_vertical_pos_level);
+        header.set_vertical_level(self.vertical_level);
+        header.set_main_entrance_screen(self.main_entrance_screen)?;
+        Ok(header)
+    }
+}
+
+impl SpriteHeaderBuilder {
+    pub fn build(&self) -> Result<SpriteHeader, LevelHeaderError> {
+        let mut header = SpriteHeader(0);
+        header.set_sprite_buoyancy(self.sprite_buoyancy);
+        header.set_disable_layer2_interaction(self.disable_layer2_interaction);
+        header.set_sprite_memory(self.sprite_memory)?;
+        Ok(header)
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for LevelMode {
+    fn default() -> Self {
+        LevelMode::Horizontal
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for Music {
+    fn default() -> Self {
+        Music::Overworld
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for Timer {
+    fn default() -> Self {
+        Timer::Disabled
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for ItemMemory {
+    fn default() -> Self {
+        ItemMemory::Table0
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for VerticalScroll {
+    fn default() -> Self {
+        VerticalScroll::AtWill
+    }
+}
+
+impl From<u8> for LevelMode {
+    fn from(mode: u8) -> Self {
+        match mode {
+            0x00 => LevelMode::Horizontal,
+            0x01 => LevelMode::HorizontalLayer2,
+            0x07 => LevelMode::Vertical,
+            0x08 => LevelMode::VerticalLayer2,
+            0x0C => LevelMode::HorizontalDarkBackground,
+            0x1E => LevelMode::HorizontalTranslucent,
+            0x1F => LevelMode::HorizontalTranslucentLayer2,
+            other => LevelMode::Other(OtherLevelMode(other)),
+        }
+    }
+}
+
+impl OtherLevelMode {
+    pub fn value(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<LevelMode> for u8 {
+    fn from(mode: LevelMode) -> Self {
+        match mode {
+            LevelMode::Horizontal => 0x00,
+            LevelMode::HorizontalLayer2 => 0x01,
+            LevelMode::Vertical => 0x07,
+            LevelMode::VerticalLayer2 => 0x08,
+            LevelMode::HorizontalDarkBackground => 0x0C,
+            LevelMode::HorizontalTranslucent => 0x1E,
+            LevelMode::HorizontalTranslucentLayer2 => 0x1F,
+            LevelMode::Other(other) => other.value(),
+        }
+    }
+}
+
+impl Timer {
+    pub fn seconds(self) -> u16 {
+        match self {
+            Timer::Disabled => 0,
+            Timer::Seconds200 => 200,
+            Timer::Seconds300 => 300,
+            Timer::Seconds400 => 400,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn check(field: &'static str, value: u8, max: u8) -> Result<u8, LevelHeaderError> {
+    if value <= max {
+        Ok(value)
+    } else {
+        Err(LevelHeaderError::OutOfRange { field, value, max })
+    }
+}
+
+fn set_bits(byte: &mut u8, shift: u8, mask: u8, value: u8) {
+    *byte = (*byte & !(mask << shift)) | ((value & mask) << shift);
 }
@@ -5,23 +5,58 @@ use nom::{
 
 pub use self::{
     background::{BackgroundData, BackgroundTileID},
-    headers::{PrimaryHeader, SecondaryHeader, SpriteHeader, PRIMARY_HEADER_SIZE, SPRITE_HEADER_SIZE},
+    headers::{
+        ItemMemory,
+        LevelMode,
+        Music,
+        OtherLevelMode,
+        PrimaryHeader,
+        PrimaryHeaderBuilder,
+        SecondaryHeader,
+        SecondaryHeaderBuilder,
+        SpriteHeader,
+        SpriteHeaderBuilder,
+        Timer,
+        VerticalScroll,
+        PRIMARY_HEADER_SIZE,
+        SPRITE_HEADER_SIZE,
+    },
     object_layer::ObjectLayer,
     sprite_layer::SpriteLayer,
+    tilemap::{render_objects, RenderedLayer},
 };
 use crate::{
-    error::LevelParseError,
-    snes_utils::{addr::AddrSnes, rom::Rom, rom_slice::SnesSlice},
+    compression::lc_rle1,
+    error::{FreeSpaceError, LevelParseError, LevelWriteError},
+    snes_utils::{
+        addr::{AddrPc, AddrSnes},
+        free_space::{FreeSpace, RatsTag, RATS_TAG_SIZE},
+        rom::Rom,
+        rom_slice::{PcSlice, SnesSlice},
+    },
 };
 
 pub mod background;
 pub mod headers;
+pub mod object_catalogue;
 pub mod object_layer;
 pub mod secondary_entrance;
 pub mod sprite_layer;
+pub mod tilemap;
 
 pub const LEVEL_COUNT: usize = 0x200;
 
+const LAYER1_DATA: AddrSnes = AddrSnes(0x05E000);
+const LAYER2_DATA: AddrSnes = AddrSnes(0x05E600);
+const SPRITE_DATA: AddrSnes = AddrSnes(0x05EC00);
+
+// Sprite data and Layer2 background pointers only hold the low 16 bits of the address, and the game
+// hardcodes the bank byte when loading them ($07 for sprites, $0C for backgrounds). A vanilla ROM
+// has no free space left in either bank, so changed data is written over the old data whenever it
+// fits (see `store_in_bank`), and only moved if it grew.
+const SPRITE_DATA_BANK: SnesSlice = SnesSlice::new(AddrSnes(0x078000), 0x8000);
+const BACKGROUND_DATA_BANK: SnesSlice = SnesSlice::new(AddrSnes(0x0C8000), 0x8000);
+
 #[derive(Clone)]
 pub enum Layer2Data {
     Background(BackgroundData),
@@ -50,57 +85,261 @@ impl Level {
     }
 
     fn parse_ph_and_l1(rom: &Rom, level_num: usize) -> Result<(PrimaryHeader, ObjectLayer), LevelParseError> {
-        const LAYER1_DATA: AddrSnes = AddrSnes(0x05E000);
-
         let l1_ptr_slice = SnesSlice::new(LAYER1_DATA + (3 * level_num), 3);
         let ph_addr = rom
-            .parse_slice_lorom(l1_ptr_slice, map(le_u24, AddrSnes::from))
+            .parse_slice_snes(l1_ptr_slice, map(le_u24, AddrSnes::from))
             .map_err(LevelParseError::Layer1AddressRead)?;
 
         let ph_slice = SnesSlice::new(ph_addr, PRIMARY_HEADER_SIZE);
-        let primary_header = rom.slice_lorom(ph_slice).map_err(LevelParseError::PrimaryHeaderRead)?;
+        let primary_header = rom.slice_snes(ph_slice).map_err(LevelParseError::PrimaryHeaderRead)?;
         let primary_header = PrimaryHeader::new(primary_header);
 
         let layer1_slice = ph_slice.skip_forward(1).infinite();
-        let layer1 = rom.parse_slice_lorom(layer1_slice, ObjectLayer::parse).map_err(LevelParseError::Layer1Read)?;
+        let layer1 = rom.parse_slice_snes(layer1_slice, ObjectLayer::parse).map_err(LevelParseError::Layer1Read)?;
 
         Ok((primary_header, layer1))
     }
 
     fn parse_l2(rom: &Rom, level_num: usize) -> Result<Layer2Data, LevelParseError> {
-        const LAYER2_DATA: AddrSnes = AddrSnes(0x05E600);
-
         let l2_addr_slice = SnesSlice::new(LAYER2_DATA + (3 * level_num), 3);
         let l2_ptr = rom
-            .parse_slice_lorom(l2_addr_slice, map(le_u24, AddrSnes::from))
+            .parse_slice_snes(l2_addr_slice, map(le_u24, AddrSnes::from))
             .map_err(LevelParseError::Layer2AddressRead)?;
 
         if (l2_ptr.0 >> 16) == 0xFF {
-            let slice = SnesSlice::new(((l2_ptr & 0xFFFF) | 0x0C0000) + PRIMARY_HEADER_SIZE, usize::MAX);
-            let layer2 = rom.slice_lorom(slice).map_err(LevelParseError::Layer2Isolate)?;
+            let slice = SnesSlice::new(background_addr(l2_ptr), usize::MAX);
+            let layer2 = rom.slice_snes(slice).map_err(LevelParseError::Layer2Isolate)?;
             let background = BackgroundData::read_from(layer2).map_err(LevelParseError::Layer2BackgroundRead)?;
             Ok(Layer2Data::Background(background))
         } else {
             let slice = SnesSlice::new(l2_ptr + PRIMARY_HEADER_SIZE, usize::MAX);
-            let objects = rom.parse_slice_lorom(slice, ObjectLayer::parse).map_err(LevelParseError::Layer2Read)?;
+            let objects = rom.parse_slice_snes(slice, ObjectLayer::parse).map_err(LevelParseError::Layer2Read)?;
             Ok(Layer2Data::Objects(objects))
         }
     }
 
     fn parse_sh_and_sl(rom: &Rom, level_num: usize) -> Result<(SpriteHeader, SpriteLayer), LevelParseError> {
-        const SPRITE_DATA: AddrSnes = AddrSnes(0x05EC00);
-
         let sprite_ptr_slice = SnesSlice::new(SPRITE_DATA + (2 * level_num), 2);
-        let sh_addr = rom.parse_slice_lorom(sprite_ptr_slice, le_u16).map_err(LevelParseError::SpriteAddressRead)?;
+        let sh_addr = rom.parse_slice_snes(sprite_ptr_slice, le_u16).map_err(LevelParseError::SpriteAddressRead)?;
         let sh_addr = AddrSnes((sh_addr as u32 | 0x07_0000) as usize);
 
         let sh_slice = SnesSlice::new(sh_addr, SPRITE_HEADER_SIZE);
         let sprite_header =
-            rom.parse_slice_lorom(sh_slice, SpriteHeader::read_from).map_err(LevelParseError::SpriteHeaderRead)?;
+            rom.parse_slice_snes(sh_slice, SpriteHeader::read_from).map_err(LevelParseError::SpriteHeaderRead)?;
 
         let sl_slice = SnesSlice::new(sh_addr + 1, usize::MAX);
-        let sprite_layer = rom.parse_slice_lorom(sl_slice, SpriteLayer::parse).map_err(LevelParseError::SpriteRead)?;
+        let sprite_layer = rom.parse_slice_snes(sl_slice, SpriteLayer::parse).map_err(LevelParseError::SpriteRead)?;
 
         Ok((sprite_header, sprite_layer))
     }
+
+    pub fn screen_count(&self) -> usize {
+        self.primary_header.level_length() as usize + 1
+    }
+
+    /// Renders Layer1 with the objects of tileset `fg_bg_gfx`, which is normally
+    /// `self.primary_header.fg_bg_gfx()` but can be changed to preview the level in another tileset.
+    pub fn render_layer1(&self, fg_bg_gfx: u8) -> RenderedLayer {
+        render_objects(&self.layer1, self.screen_count(), self.secondary_header.vertical_level(), fg_bg_gfx)
+    }
+
+    /// Renders Layer2 like `render_layer1` if it's made of objects rather than a background.
+    pub fn render_layer2(&self, fg_bg_gfx: u8) -> Option<RenderedLayer> {
+        match &self.layer2 {
+            Layer2Data::Objects(objects) => {
+                Some(render_objects(objects, self.screen_count(), self.secondary_header.vertical_level(), fg_bg_gfx))
+            }
+            Layer2Data::Background(_) => None,
+        }
+    }
+
+    /// Encodes every part of the level and points the level's pointer tables at it. Parts that
+    /// changed are moved to free space, and their old location is freed if it's protected by a RATS
+    /// tag and no other pointer table entry points into it. Untagged data is left alone. Sprite data
+    /// and backgrounds are the exception: they're overwritten in place when they fit and aren't shared.
+    pub fn write(&self, rom: &mut Rom, free_space: &mut FreeSpace, level_num: usize) -> Result<(), LevelWriteError> {
+        self.write_ph_and_l1(rom, free_space, level_num)?;
+        self.write_l2(rom, free_space, level_num)?;
+        self.write_sh_and_sl(rom, free_space, level_num)?;
+        self.secondary_header.write_to_rom(rom, level_num).map_err(LevelWriteError::SecondaryHeaderWrite)
+    }
+
+    fn write_ph_and_l1(
+        &self, rom: &mut Rom, free_space: &mut FreeSpace, level_num: usize,
+    ) -> Result<(), LevelWriteError> {
+        let l1_ptr_slice = SnesSlice::new(LAYER1_DATA + (3 * level_num), 3);
+        let old_addr = rom
+            .parse_slice_snes(l1_ptr_slice, map(le_u24, AddrSnes::from))
+            .map_err(LevelWriteError::Layer1AddressRead)?;
+
+        let mut data = self.primary_header.to_bytes().to_vec();
+        data.extend(self.layer1.to_bytes());
+        let new_addr =
+            store(rom, free_space, l1_ptr_slice.begin, Some(old_addr), &data, None, LevelWriteError::Layer1Allocate)?;
+
+        rom.write_snes(l1_ptr_slice.begin, &(new_addr.0 as u32).to_le_bytes()[..3])
+            .map_err(LevelWriteError::PointerWrite)
+    }
+
+    fn write_l2(&self, rom: &mut Rom, free_space: &mut FreeSpace, level_num: usize) -> Result<(), LevelWriteError> {
+        let l2_ptr_slice = SnesSlice::new(LAYER2_DATA + (3 * level_num), 3);
+        let old_ptr = rom
+            .parse_slice_snes(l2_ptr_slice, map(le_u24, AddrSnes::from))
+            .map_err(LevelWriteError::Layer2AddressRead)?;
+        let old_is_background = (old_ptr.0 >> 16) == 0xFF;
+
+        let new_ptr = match &self.layer2 {
+            Layer2Data::Background(background) => {
+                let old_addr = old_is_background.then(|| background_addr(old_ptr));
+                let unchanged = old_addr
+                    .and_then(|addr| rom.slice_snes(SnesSlice::new(addr, usize::MAX)).ok())
+                    .and_then(|bytes| BackgroundData::read_from(bytes).ok())
+                    .as_ref()
+                    == Some(background);
+                if unchanged {
+                    old_ptr
+                } else {
+                    let data = background.to_bytes();
+                    let old_data = old_addr.and_then(|addr| {
+                        let bytes = rom.slice_snes(SnesSlice::new(addr, usize::MAX)).ok()?;
+                        let old_len = lc_rle1::decompress(bytes).ok()?.input_len;
+                        Some((addr, old_len))
+                    });
+                    let new_addr = store_in_bank(
+                        rom,
+                        free_space,
+                        l2_ptr_slice.begin,
+                        old_data,
+                        &data,
+                        BACKGROUND_DATA_BANK,
+                        LevelWriteError::Layer2Allocate,
+                    )?;
+                    AddrSnes(((new_addr - PRIMARY_HEADER_SIZE).0 & 0xFFFF) | 0xFF0000)
+                }
+            }
+            Layer2Data::Objects(objects) => {
+                // The game skips the header of Layer2 objects, so keep whatever was there before.
+                let old_addr = if old_is_background { None } else { Some(old_ptr) };
+                let mut data = old_addr
+                    .and_then(|addr| rom.slice_snes(SnesSlice::new(addr, PRIMARY_HEADER_SIZE)).ok())
+                    .map_or_else(|| self.primary_header.to_bytes().to_vec(), <[u8]>::to_vec);
+                data.extend(objects.to_bytes());
+                store(rom, free_space, l2_ptr_slice.begin, old_addr, &data, None, LevelWriteError::Layer2Allocate)?
+            }
+        };
+
+        rom.write_snes(l2_ptr_slice.begin, &(new_ptr.0 as u32).to_le_bytes()[..3])
+            .map_err(LevelWriteError::PointerWrite)
+    }
+
+    fn write_sh_and_sl(
+        &self, rom: &mut Rom, free_space: &mut FreeSpace, level_num: usize,
+    ) -> Result<(), LevelWriteError> {
+        let sprite_ptr_slice = SnesSlice::new(SPRITE_DATA + (2 * level_num), 2);
+        let old_addr = rom.parse_slice_snes(sprite_ptr_slice, le_u16).map_err(LevelWriteError::SpriteAddressRead)?;
+        let old_addr = AddrSnes((old_addr as u32 | 0x07_0000) as usize);
+
+        let old_data = rom.slice_snes(SnesSlice::new(old_addr + 1, usize::MAX)).ok().and_then(|bytes| {
+            let (rest, _) = SpriteLayer::parse(bytes).ok()?;
+            Some((old_addr, 1 + bytes.len() - rest.len()))
+        });
+
+        let mut data = vec![self.sprite_header.to_byte()];
+        data.extend(self.sprite_layer.to_bytes());
+        let new_addr = store_in_bank(
+            rom,
+            free_space,
+            sprite_ptr_slice.begin,
+            old_data,
+            &data,
+            SPRITE_DATA_BANK,
+            LevelWriteError::SpriteAllocate,
+        )?;
+
+        rom.write_snes(sprite_ptr_slice.begin, &(new_addr.0 as u16).to_le_bytes())
+            .map_err(LevelWriteError::PointerWrite)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn background_addr(l2_ptr: AddrSnes) -> AddrSnes {
+    ((l2_ptr & 0xFFFF) | 0x0C0000) + PRIMARY_HEADER_SIZE
+}
+
+/// Whether any entry of the level pointer tables other than the one at `pointer` points into `region`.
+fn is_referenced_elsewhere(rom: &Rom, pointer: AddrSnes, region: PcSlice) -> bool {
+    let mapper = rom.mapper();
+    let tables = [(LAYER1_DATA, 3), (LAYER2_DATA, 3), (SPRITE_DATA, 2)];
+    let targets = tables
+        .iter()
+        .flat_map(|&(table, entry_size)| (0..LEVEL_COUNT).map(move |n| (table, entry_size, table + (entry_size * n))));
+    targets.filter(|&(_, _, entry)| entry != pointer).any(|(table, entry_size, entry)| {
+        let bytes = match rom.slice_snes(SnesSlice::new(entry, entry_size)) {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+        let value = bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as usize);
+        let addr = if table == SPRITE_DATA {
+            AddrSnes(value | 0x070000)
+        } else if table == LAYER2_DATA && (value >> 16) == 0xFF {
+            background_addr(AddrSnes(value))
+        } else {
+            AddrSnes(value)
+        };
+        let begin = region.begin.0;
+        matches!(mapper.to_pc(addr), Ok(pc) if (begin..begin + region.size).contains(&pc.0))
+    })
+}
+
+/// Like `store`, but for data that has to stay in `bank`. If it fits in the `old_data` block (address
+/// and length) and no other pointer points into that block, the data is written over it in place.
+fn store_in_bank(
+    rom: &mut Rom, free_space: &mut FreeSpace, pointer: AddrSnes, old_data: Option<(AddrSnes, usize)>, data: &[u8],
+    bank: SnesSlice, allocation_error: fn(FreeSpaceError) -> LevelWriteError,
+) -> Result<AddrSnes, LevelWriteError> {
+    if let Some((old_addr, old_len)) = old_data {
+        let old_region = rom.mapper().to_pc(old_addr).map(|pc| PcSlice::new(pc, old_len));
+        let shared = old_region.map_or(true, |region| is_referenced_elsewhere(rom, pointer, region));
+        if !shared && data.len() <= old_len {
+            rom.write_snes(old_addr, data).map_err(LevelWriteError::DataWrite)?;
+            return Ok(old_addr);
+        }
+    }
+    store(rom, free_space, pointer, old_data.map(|(addr, _)| addr), data, Some(bank), allocation_error)
+}
+
+/// Returns the address of `data`, which replaces `old_addr` in the pointer table entry at `pointer`:
+/// `old_addr` if the data there is the same, otherwise a new block allocated in free space, optionally
+/// restricted to `bank`.
+fn store(
+    rom: &mut Rom, free_space: &mut FreeSpace, pointer: AddrSnes, old_addr: Option<AddrSnes>, data: &[u8],
+    bank: Option<SnesSlice>, allocation_error: fn(FreeSpaceError) -> LevelWriteError,
+) -> Result<AddrSnes, LevelWriteError> {
+    if let Some(old_addr) = old_addr {
+        if matches!(rom.slice_snes(SnesSlice::new(old_addr, data.len())), Ok(old) if old == data) {
+            return Ok(old_addr);
+        }
+    }
+
+    let mapper = rom.mapper();
+    let new_addr = match bank {
+        Some(bank) => {
+            let begin = mapper.to_pc(bank.begin).map_err(LevelWriteError::Address)?;
+            free_space.allocate_in(rom, data, PcSlice::new(begin, bank.size))
+        }
+        None => free_space.allocate(rom, data),
+    }
+    .map_err(allocation_error)?;
+
+    if let Some(old_pc) = old_addr.and_then(|addr| mapper.to_pc(addr).ok()) {
+        let tag = old_pc.0.checked_sub(RATS_TAG_SIZE).and_then(|tag| RatsTag::read(rom, AddrPc(tag)));
+        if let Some(tag) = tag {
+            if !is_referenced_elsewhere(rom, pointer, tag.block()) {
+                free_space.free(rom, old_pc).map_err(LevelWriteError::FreeOldData)?;
+            }
+        }
+    }
+
+    mapper.to_snes(new_addr).map_err(LevelWriteError::Address)
 }
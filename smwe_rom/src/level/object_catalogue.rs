@@ -0,0 +1,238 @@
+use std::{fmt, ops::RangeInclusive};
+
+use crate::level::object_layer::{ExtendedObjectID, NonExitInstance, StandardObjectID};
+
+/// How the settings byte of an object is interpreted. Sizes are stored as one less than the actual
+/// size, so a nibble of 0 means 1 block.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SettingsKind {
+    /// `HHHHWWWW`: height and width.
+    Size,
+    /// `----WWWW`: width.
+    Width,
+    /// `----HHHH`: height.
+    Height,
+    /// `WWWWWWWW`: width.
+    LongWidth,
+    /// `TTTTWWWW`: variant of the object and its width.
+    TypeAndWidth,
+    /// `TTTTHHHH`: variant of the object and its height.
+    TypeAndHeight,
+    /// No settings, the byte is the extended object number.
+    None,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ObjectSettings {
+    Size { width: u8, height: u8 },
+    Width(u16),
+    Height(u8),
+    TypeAndWidth { variant: u8, width: u8 },
+    TypeAndHeight { variant: u8, height: u8 },
+    None,
+}
+
+/// Groups of tilesets sharing the same tileset-specific objects.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ObjectTileset {
+    Normal,
+    Castle,
+    Rope,
+    Underground,
+    GhostHouse,
+}
+
+/// Set of `ObjectTileset`s an object can be used in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ObjectTilesets(u8);
+
+#[derive(Copy, Clone, Debug)]
+pub struct ObjectInfo {
+    pub name:     &'static str,
+    pub settings: SettingsKind,
+    pub tilesets: ObjectTilesets,
+}
+
+pub const STANDARD_OBJECTS: RangeInclusive<StandardObjectID> = 0x01..=0x3F;
+pub const TILESET_SPECIFIC_OBJECTS: RangeInclusive<StandardObjectID> = 0x22..=0x3F;
+
+// -------------------------------------------------------------------------------------------------
+
+impl SettingsKind {
+    pub fn decode(self, settings: u8) -> ObjectSettings {
+        let (hi, lo) = (settings >> 4, settings & 0b1111);
+        match self {
+            SettingsKind::Size => ObjectSettings::Size { width: lo + 1, height: hi + 1 },
+            SettingsKind::Width => ObjectSettings::Width(lo as u16 + 1),
+            SettingsKind::Height => ObjectSettings::Height(lo + 1),
+            SettingsKind::LongWidth => ObjectSettings::Width(settings as u16 + 1),
+            SettingsKind::TypeAndWidth => ObjectSettings::TypeAndWidth { variant: hi, width: lo + 1 },
+            SettingsKind::TypeAndHeight => ObjectSettings::TypeAndHeight { variant: hi, height: lo + 1 },
+            SettingsKind::None => ObjectSettings::None,
+        }
+    }
+}
+
+impl fmt::Display for ObjectSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectSettings::Size { width, height } => write!(f, "width {}, height {}", width, height),
+            ObjectSettings::Width(width) => write!(f, "width {}", width),
+            ObjectSettings::Height(height) => write!(f, "height {}", height),
+            ObjectSettings::TypeAndWidth { variant, width } => write!(f, "type {}, width {}", variant, width),
+            ObjectSettings::TypeAndHeight { variant, height } => write!(f, "type {}, height {}", variant, height),
+            ObjectSettings::None => Ok(()),
+        }
+    }
+}
+
+impl ObjectTileset {
+    pub const ALL: [ObjectTileset; 5] = {
+        use ObjectTileset::*;
+        [Normal, Castle, Rope, Underground, GhostHouse]
+    };
+
+    /// Object set used by a tileset, as selected by `PrimaryHeader::fg_bg_gfx`.
+    pub fn from_fg_bg_gfx(fg_bg_gfx: u8) -> Option<Self> {
+        use ObjectTileset::*;
+        [
+            Normal,      // Normal 1
+            Castle,      // Castle 1
+            Rope,        // Rope 1
+            Underground, // Underground 1
+            Normal,      // Switch Palace 1
+            GhostHouse,  // Ghost House 1
+            Rope,        // Rope 2
+            Normal,      // Normal 2
+            Rope,        // Rope 3
+            Underground, // Underground 2
+            Normal,      // Switch Palace 2
+            Castle,      // Castle 2
+            Normal,      // Cloud/Forest
+            GhostHouse,  // Ghost House 2
+            Underground, // Underground 3
+        ]
+        .get(fg_bg_gfx as usize)
+        .copied()
+    }
+}
+
+impl ObjectTilesets {
+    pub const ALL: Self = Self(0b11111);
+
+    pub const fn only(tileset: ObjectTileset) -> Self {
+        Self(1 << tileset as u8)
+    }
+
+    pub fn contains(self, tileset: ObjectTileset) -> bool {
+        self.0 & Self::only(tileset).0 != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = ObjectTileset> {
+        ObjectTileset::ALL.iter().copied().filter(move |&tileset| self.contains(tileset))
+    }
+}
+
+impl ObjectInfo {
+    /// Catalogue entry of a standard object. Objects 0x22-0x3F depend on the object set of
+    /// `tileset` and aren't catalogued yet, so they have no entry.
+    pub fn standard(std_obj_num: StandardObjectID, tileset: ObjectTileset) -> Option<&'static Self> {
+        if TILESET_SPECIFIC_OBJECTS.contains(&std_obj_num) {
+            log::debug!("Standard object {:#04X} of the {:?} object set isn't catalogued", std_obj_num, tileset);
+            None
+        } else {
+            (std_obj_num as usize).checked_sub(1).and_then(|i| COMMON_OBJECT_INFO.get(i))
+        }
+    }
+
+    /// Catalogue entry of an extended object. Only some of the vanilla extended objects are
+    /// catalogued so far, the others have no entry.
+    pub fn extended(ext_obj_num: ExtendedObjectID) -> Option<&'static Self> {
+        EXTENDED_OBJECT_INFO.iter().find(|(num, _)| *num == ext_obj_num).map(|(_, info)| info)
+    }
+
+    /// Catalogue entry of a standard or extended object in a level using `tileset`.
+    pub fn of(instance: &NonExitInstance, tileset: ObjectTileset) -> Option<&'static Self> {
+        match instance.ext_obj_num() {
+            Some(ext_obj_num) => Self::extended(ext_obj_num),
+            None => Self::standard(instance.std_obj_num(), tileset),
+        }
+    }
+
+    /// Name of the object together with the meaning of its settings, e.g. "Coins (width 3, height 2)".
+    pub fn describe(instance: &NonExitInstance, tileset: ObjectTileset) -> String {
+        match Self::of(instance, tileset) {
+            Some(info) => match info.settings.decode(instance.settings()) {
+                ObjectSettings::None => info.name.to_string(),
+                settings => format!("{} ({})", info.name, settings),
+            },
+            None => match instance.ext_obj_num() {
+                Some(ext_obj_num) => format!("Extended object {:#04X}", ext_obj_num),
+                None => {
+                    format!("Standard object {:#04X} (settings {:#04X})", instance.std_obj_num(), instance.settings())
+                }
+            },
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+const fn common(name: &'static str, settings: SettingsKind) -> ObjectInfo {
+    ObjectInfo { name, settings, tilesets: ObjectTilesets::ALL }
+}
+
+const fn extended(name: &'static str) -> ObjectInfo {
+    ObjectInfo { name, settings: SettingsKind::None, tilesets: ObjectTilesets::ALL }
+}
+
+#[rustfmt::skip]
+static COMMON_OBJECT_INFO: [ObjectInfo; 0x21] = [
+    common("Water (blue)",                    SettingsKind::Size),          // 01
+    common("Invisible coin blocks",           SettingsKind::Size),          // 02
+    common("Invisible note blocks",           SettingsKind::Size),          // 03
+    common("Invisible POW coins",             SettingsKind::Size),          // 04
+    common("Coins",                           SettingsKind::Size),          // 05
+    common("Walk-through dirt",               SettingsKind::Size),          // 06
+    common("Water (other color)",             SettingsKind::Size),          // 07
+    common("Note blocks",                     SettingsKind::Size),          // 08
+    common("Turn blocks",                     SettingsKind::Size),          // 09
+    common("Coin ?-blocks",                   SettingsKind::Size),          // 0A
+    common("Throw blocks",                    SettingsKind::Size),          // 0B
+    common("Black piranha plants",            SettingsKind::Size),          // 0C
+    common("Cement blocks",                   SettingsKind::Size),          // 0D
+    common("Brown blocks",                    SettingsKind::Size),          // 0E
+    common("Vertical pipe",                   SettingsKind::TypeAndHeight), // 0F
+    common("Horizontal pipe",                 SettingsKind::TypeAndWidth),  // 10
+    common("Bullet shooter",                  SettingsKind::Height),        // 11
+    common("Slopes",                          SettingsKind::TypeAndWidth),  // 12
+    common("Ledge edges",                     SettingsKind::TypeAndHeight), // 13
+    common("Ground ledge",                    SettingsKind::Size),          // 14
+    common("Midway/Goal point",               SettingsKind::TypeAndHeight), // 15
+    common("Blue coins",                      SettingsKind::Size),          // 16
+    common("Rope/Clouds",                     SettingsKind::TypeAndWidth),  // 17
+    common("Water surface (animated)",        SettingsKind::Size),          // 18
+    common("Water surface (not animated)",    SettingsKind::Size),          // 19
+    common("Lava surface (animated)",         SettingsKind::Size),          // 1A
+    common("Net top edge",                    SettingsKind::Width),         // 1B
+    common("Donut bridge",                    SettingsKind::Size),          // 1C
+    common("Net bottom edge",                 SettingsKind::Width),         // 1D
+    common("Net vertical edge",               SettingsKind::TypeAndHeight), // 1E
+    common("Vertical pipe/bone/log",          SettingsKind::TypeAndHeight), // 1F
+    common("Horizontal pipe/bone/log",        SettingsKind::TypeAndWidth),  // 20
+    common("Long ground ledge",               SettingsKind::LongWidth),     // 21
+];
+
+#[rustfmt::skip]
+static EXTENDED_OBJECT_INFO: [(ExtendedObjectID, ObjectInfo); 10] = [
+    (0x00, extended("Screen exit")),
+    (0x01, extended("Screen jump")),
+    (0x10, extended("3-Up moon")),
+    (0x11, extended("Invisible 1-Up point 1")),
+    (0x12, extended("Invisible 1-Up point 2")),
+    (0x13, extended("Invisible 1-Up point 3")),
+    (0x14, extended("Invisible 1-Up point 4")),
+    (0x15, extended("Red berry")),
+    (0x16, extended("Pink berry")),
+    (0x17, extended("Green berry")),
+];
@@ -1,13 +1,25 @@
-use std::convert::TryInto;
+use std::{convert::TryInto, slice::Iter};
 
 use nom::{cond, do_parse, many_till, tag, take, IResult};
 
+use crate::error::ObjectError;
+
 pub const NON_EXIT_INSTANCE_SIZE: usize = 3;
 pub const EXIT_INSTANCE_SIZE: usize = 4;
 
+pub const MAX_SCREEN: u8 = 0x1F;
+
 pub type StandardObjectID = u8;
 pub type ExtendedObjectID = u8;
 
+/// Size of a screen in blocks. Screens of horizontal levels are laid out in a row, and those of
+/// vertical levels in a column, with the highest bit of the Y position picking the screen's half.
+pub const HORIZONTAL_SCREEN_SIZE: (u32, u32) = (16, 27);
+pub const VERTICAL_SCREEN_SIZE: (u32, u32) = (32, 16);
+
+const EXIT_EXT_OBJ_NUM: ExtendedObjectID = 0;
+const SCREEN_JUMP_EXT_OBJ_NUM: ExtendedObjectID = 1;
+
 #[derive(Clone)]
 pub struct ExitInstance([u8; EXIT_INSTANCE_SIZE]);
 
@@ -24,12 +36,28 @@ pub enum ObjectInstance {
     ScreenJump(ScreenJumpInstance),
 }
 
-#[derive(Clone)]
+#[derive(Copy, Clone)]
+pub struct PositionedObject<'a> {
+    pub index:     usize,
+    pub screen:    u8,
+    /// Position in blocks from the top-left corner of the level. Exits and screen jumps don't have one.
+    pub block_pos: Option<(u32, u32)>,
+    pub object:    &'a ObjectInstance,
+}
+
+#[derive(Clone, Default)]
 pub struct ObjectLayer {
     objects: Vec<ObjectInstance>,
 }
 
 impl ExitInstance {
+    pub fn new(screen_number: u8, secondary_exit: bool, destination_level: u16) -> Result<Self, ObjectError> {
+        check("screen_number", screen_number as u16, MAX_SCREEN as u16)?;
+        check("destination_level", destination_level, 0x1FF)?;
+        let hi = ((secondary_exit as u8) << 1) | (destination_level >> 8) as u8;
+        Ok(Self([screen_number, hi, EXIT_EXT_OBJ_NUM, destination_level as u8]))
+    }
+
     pub fn screen_number(&self) -> u8 {
         // ---ppppp -------- -------- --------
         // screen_number = ppppp
@@ -52,6 +80,27 @@ impl ExitInstance {
 }
 
 impl NonExitInstance {
+    pub fn new_standard((x, y): (u8, u8), std_obj_num: StandardObjectID, settings: u8) -> Result<Self, ObjectError> {
+        if std_obj_num == 0 {
+            return Err(ObjectError::ReservedStandardObject);
+        }
+        check("std_obj_num", std_obj_num as u16, 0x3F)?;
+        Self::new((x, y), std_obj_num, settings)
+    }
+
+    pub fn new_extended((x, y): (u8, u8), ext_obj_num: ExtendedObjectID) -> Result<Self, ObjectError> {
+        if ext_obj_num == EXIT_EXT_OBJ_NUM || ext_obj_num == SCREEN_JUMP_EXT_OBJ_NUM {
+            return Err(ObjectError::ReservedExtendedObject(ext_obj_num));
+        }
+        Self::new((x, y), 0, ext_obj_num)
+    }
+
+    fn new((x, y): (u8, u8), std_obj_num: StandardObjectID, settings: u8) -> Result<Self, ObjectError> {
+        check("x", x as u16, 0xF)?;
+        check("y", y as u16, 0x1F)?;
+        Ok(Self([((std_obj_num & 0b110000) << 1) | y, ((std_obj_num & 0b1111) << 4) | x, settings]))
+    }
+
     pub fn new_screen(&self) -> bool {
         // N----- -------- --------
         // new_screen = N
@@ -84,6 +133,17 @@ impl NonExitInstance {
         (x, y)
     }
 
+    pub fn absolute_pos(&self, screen: u8, vertical_level: bool) -> (u32, u32) {
+        let (x, y) = self.xy_pos();
+        let (x, y, screen) = (x as u32, y as u32, screen as u32);
+        if vertical_level {
+            let (half_width, height) = (VERTICAL_SCREEN_SIZE.0 / 2, VERTICAL_SCREEN_SIZE.1);
+            ((y / height) * half_width + x, screen * height + y % height)
+        } else {
+            (screen * HORIZONTAL_SCREEN_SIZE.0 + x, y)
+        }
+    }
+
     pub fn settings(&self) -> u8 {
         // -------- -------- SSSSSSSS
         // settings = SSSSSSSS
@@ -96,6 +156,11 @@ impl NonExitInstance {
 }
 
 impl ScreenJumpInstance {
+    pub fn new(screen_number: u8) -> Result<Self, ObjectError> {
+        check("screen_number", screen_number as u16, MAX_SCREEN as u16)?;
+        Ok(Self([screen_number, 0, SCREEN_JUMP_EXT_OBJ_NUM]))
+    }
+
     pub fn screen_number(&self) -> u8 {
         // ---HHHHH -------- --------
         // screen_number = HHHHH
@@ -104,6 +169,47 @@ impl ScreenJumpInstance {
 }
 
 impl ObjectInstance {
+    /// Screen the object is placed on. Unlike the other objects, the position of a `NonExitInstance`
+    /// is relative to the screen of the objects before it, so it needs to be passed in.
+    fn screen_number(&self, current_screen: u8) -> u8 {
+        match self {
+            ObjectInstance::Exit(i) => i.screen_number(),
+            ObjectInstance::NonExit(_) => current_screen,
+            ObjectInstance::ScreenJump(i) => i.screen_number(),
+        }
+    }
+
+    /// Places the object on `screen`, and returns whether it has to be preceded by a screen jump.
+    fn set_screen_number(&mut self, screen: u8, current_screen: u8) -> bool {
+        match self {
+            ObjectInstance::NonExit(i) => {
+                let new_screen = screen == current_screen + 1;
+                i.0[0] = (i.0[0] & 0x7F) | ((new_screen as u8) << 7);
+                screen != current_screen && !new_screen
+            }
+            ObjectInstance::Exit(i) => {
+                i.0[0] = screen;
+                false
+            }
+            ObjectInstance::ScreenJump(i) => {
+                i.0[0] = screen;
+                false
+            }
+        }
+    }
+
+    fn new_screen(&self) -> bool {
+        (self.as_bytes()[0] >> 7) != 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ObjectInstance::Exit(i) => &i.0,
+            ObjectInstance::NonExit(i) => &i.0,
+            ObjectInstance::ScreenJump(i) => &i.0,
+        }
+    }
+
     pub fn is_extended(&self) -> bool {
         match self {
             ObjectInstance::NonExit(i) => i.is_extended(),
@@ -119,13 +225,13 @@ impl ObjectLayer {
             input,
             do_parse!(
                 first_three: take!(3) >>
-                last: cond!((first_three[0] & 0b11100000) == 0 && first_three[2] == 0, take!(1)) >>
+                last: cond!(is_ext_obj(first_three, EXIT_EXT_OBJ_NUM), take!(1)) >>
                 (match last {
                     Some(l) => {
                         let instance = ExitInstance([first_three, l].concat().try_into().unwrap());
                         ObjectInstance::Exit(instance)
                     },
-                    None => if first_three[2] == 1 {
+                    None => if is_ext_obj(first_three, SCREEN_JUMP_EXT_OBJ_NUM) {
                         let instance = ScreenJumpInstance(first_three.try_into().unwrap());
                         ObjectInstance::ScreenJump(instance)
                     } else {
@@ -136,4 +242,169 @@ impl ObjectLayer {
             tag!(&[0xFFu8]))?;
         Ok((input, Self { objects }))
     }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ObjectInstance> {
+        self.objects.get(index)
+    }
+
+    pub fn iter(&self) -> Iter<'_, ObjectInstance> {
+        self.objects.iter()
+    }
+
+    pub fn objects(&self) -> &[ObjectInstance] {
+        &self.objects
+    }
+
+    /// Screen number of every object, in order.
+    pub fn screens(&self) -> Vec<u8> {
+        let mut current_screen = 0;
+        self.objects
+            .iter()
+            .map(|object| {
+                if object.new_screen() {
+                    current_screen = (current_screen + 1) & MAX_SCREEN;
+                }
+                let screen = object.screen_number(current_screen);
+                if let ObjectInstance::ScreenJump(_) = object {
+                    current_screen = screen;
+                }
+                screen
+            })
+            .collect()
+    }
+
+    /// Every object together with its screen and, for objects placed in the level, absolute position.
+    pub fn positioned_objects(&self, vertical_level: bool) -> impl Iterator<Item = PositionedObject<'_>> {
+        self.screens().into_iter().zip(self.objects.iter()).enumerate().map(move |(index, (screen, object))| {
+            let block_pos = match object {
+                ObjectInstance::NonExit(i) => Some(i.absolute_pos(screen, vertical_level)),
+                ObjectInstance::Exit(_) | ObjectInstance::ScreenJump(_) => None,
+            };
+            PositionedObject { index, screen, block_pos, object }
+        })
+    }
+
+    pub fn push(&mut self, screen: u8, object: ObjectInstance) -> Result<(), ObjectError> {
+        self.insert(self.objects.len(), screen, object)
+    }
+
+    /// Inserts `object` on `screen` at `index`. The objects after it stay on their screens, which may
+    /// need a screen jump to be added before or after the new object.
+    pub fn insert(&mut self, index: usize, screen: u8, object: ObjectInstance) -> Result<(), ObjectError> {
+        check("screen", screen as u16, MAX_SCREEN as u16)?;
+        let mut entries = self.entries();
+        // Inserting right after the last object is fine too.
+        if index != entries.len() {
+            check_index(index, entries.len())?;
+        }
+        entries.insert(index, (screen, object));
+        self.rebuild(entries);
+        Ok(())
+    }
+
+    /// Removes the object at `index`. The objects after it stay on their screens.
+    pub fn remove(&mut self, index: usize) -> Result<ObjectInstance, ObjectError> {
+        let mut entries = self.entries();
+        check_index(index, entries.len())?;
+        let (_, object) = entries.remove(index);
+        self.rebuild(entries);
+        Ok(object)
+    }
+
+    /// Moves the object at `from` to `to`, keeping it on its screen.
+    pub fn move_object(&mut self, from: usize, to: usize) -> Result<(), ObjectError> {
+        let mut entries = self.entries();
+        check_index(from, entries.len())?;
+        check_index(to, entries.len())?;
+        let entry = entries.remove(from);
+        entries.insert(to, entry);
+        self.rebuild(entries);
+        Ok(())
+    }
+
+    /// Moves the object at `index` to `screen`, keeping its position in the list.
+    pub fn set_screen(&mut self, index: usize, screen: u8) -> Result<(), ObjectError> {
+        check("screen", screen as u16, MAX_SCREEN as u16)?;
+        let mut entries = self.entries();
+        check_index(index, entries.len())?;
+        entries[index].0 = screen;
+        self.rebuild(entries);
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<(u8, ObjectInstance)> {
+        self.screens().into_iter().zip(self.objects.iter().cloned()).collect()
+    }
+
+    /// Re-encodes the new-screen flags so that every object ends up on its screen, adding screen
+    /// jumps wherever a flag alone can't get there.
+    fn rebuild(&mut self, entries: Vec<(u8, ObjectInstance)>) {
+        self.objects.clear();
+        let mut current_screen = 0;
+        for (screen, mut object) in entries {
+            if object.set_screen_number(screen, current_screen) {
+                let jump = ScreenJumpInstance::new(screen).expect("Screen number already validated");
+                self.objects.push(ObjectInstance::ScreenJump(jump));
+            }
+            if !matches!(object, ObjectInstance::Exit(_)) {
+                current_screen = screen;
+            }
+            self.objects.push(object);
+        }
+    }
+
+    /// Encodes the objects followed by the 0xFF terminator.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.objects.iter().flat_map(|o| o.as_bytes().iter().copied()).collect();
+        bytes.push(0xFF);
+        bytes
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Splits a position in blocks from the top-left corner of the level into a screen number and the
+/// position of an object within that screen.
+pub fn relative_pos((x, y): (u32, u32), vertical_level: bool) -> Result<(u8, (u8, u8)), ObjectError> {
+    let (screen, rel_x, rel_y) = if vertical_level {
+        let (width, height) = VERTICAL_SCREEN_SIZE;
+        check("x", x.min(u16::MAX as u32) as u16, (width - 1) as u16)?;
+        let half_width = width / 2;
+        (y / height, x % half_width, ((x / half_width) << 4) | (y % height))
+    } else {
+        let (width, height) = HORIZONTAL_SCREEN_SIZE;
+        check("y", y.min(u16::MAX as u32) as u16, (height - 1) as u16)?;
+        (x / width, x % width, y)
+    };
+    check("screen", screen.min(u16::MAX as u32) as u16, MAX_SCREEN as u16)?;
+    Ok((screen as u8, (rel_x as u8, rel_y as u8)))
+}
+
+/// Exits and screen jumps are extended objects, told apart by their extended object number.
+fn is_ext_obj(first_three: &[u8], ext_obj_num: ExtendedObjectID) -> bool {
+    (first_three[0] & 0b01100000) == 0 && (first_three[1] & 0b11110000) == 0 && first_three[2] == ext_obj_num
+}
+
+fn check_index(index: usize, len: usize) -> Result<(), ObjectError> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(ObjectError::IndexOutOfBounds(index, len))
+    }
+}
+
+fn check(field: &'static str, value: u16, max: u16) -> Result<(), ObjectError> {
+    if value <= max {
+        Ok(())
+    } else {
+        Err(ObjectError::OutOfRange { field, value, max })
+    }
 }
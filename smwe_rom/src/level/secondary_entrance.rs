@@ -9,7 +9,7 @@ pub struct SecondaryEntrance([u8; 4]);
 
 impl SecondaryEntrance {
     pub fn read_from_rom(rom: &Rom, entrance_id: usize) -> Result<Self, RomError> {
-        let take_table = |table_num| rom.slice_lorom(SECONDARY_ENTRANCE_TABLE.skip_forward(table_num));
+        let take_table = |table_num| rom.slice_snes(SECONDARY_ENTRANCE_TABLE.skip_forward(table_num));
 
         let bytes = [
             take_table(0)?[entrance_id],
@@ -59,4 +59,11 @@ impl SpriteLayer {
         let sprites = sprites_raw.into_iter().map(|spr| SpriteInstance(spr.try_into().unwrap())).collect();
         Ok((input, Self { sprites }))
     }
+
+    /// Encodes the sprites followed by the 0xFF terminator.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.sprites.iter().flat_map(|s| s.0.iter().copied()).collect();
+        bytes.push(0xFF);
+        bytes
+    }
 }
@@ -0,0 +1,165 @@
+use crate::{
+    graphics::map16::Map16BlockId,
+    level::{
+        object_catalogue::{ObjectInfo, ObjectSettings, ObjectTileset, TILESET_SPECIFIC_OBJECTS},
+        object_layer::{
+            NonExitInstance,
+            ObjectInstance,
+            ObjectLayer,
+            StandardObjectID,
+            HORIZONTAL_SCREEN_SIZE,
+            MAX_SCREEN,
+            VERTICAL_SCREEN_SIZE,
+        },
+    },
+};
+
+/// Block that fills every cell no object has been placed in.
+pub const EMPTY_BLOCK: Map16BlockId = 0x025;
+
+/// Grid of Map16 blocks of a whole layer, with the level's screens laid out in a row for horizontal
+/// levels and in a column for vertical ones.
+#[derive(Clone)]
+pub struct LevelTilemap {
+    pub width:  usize,
+    pub height: usize,
+    pub blocks: Vec<Map16BlockId>,
+}
+
+pub struct RenderedLayer {
+    pub tilemap:     LevelTilemap,
+    /// Indices of objects that didn't place any blocks, either because the game has no routine for
+    /// them or because their routine hasn't been ported yet.
+    pub unsupported: Vec<usize>,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl LevelTilemap {
+    pub fn new(screens: usize, vertical_level: bool) -> Self {
+        let (width, height) = if vertical_level {
+            (VERTICAL_SCREEN_SIZE.0 as usize, VERTICAL_SCREEN_SIZE.1 as usize * screens)
+        } else {
+            (HORIZONTAL_SCREEN_SIZE.0 as usize * screens, HORIZONTAL_SCREEN_SIZE.1 as usize)
+        };
+        Self { width, height, blocks: vec![EMPTY_BLOCK; width * height] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Map16BlockId> {
+        if x < self.width && y < self.height {
+            Some(self.blocks[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Places a block, ignoring ones outside of the level just like the game does.
+    pub fn set(&mut self, x: usize, y: usize, block: Map16BlockId) {
+        if x < self.width && y < self.height {
+            self.blocks[y * self.width + x] = block;
+        }
+    }
+
+    pub fn fill(&mut self, (x, y): (usize, usize), (width, height): (usize, usize), block: Map16BlockId) {
+        for row in y..y + height {
+            for column in x..x + width {
+                self.set(column, row, block);
+            }
+        }
+    }
+}
+
+/// Runs the routine of every object in order, so that later objects overwrite earlier ones. The
+/// layer spans `screens` screens and uses tileset `fg_bg_gfx`. Objects that couldn't be rendered are
+/// reported in `RenderedLayer::unsupported`; that includes every object 0x22-0x3F, since none of the
+/// tileset-specific routines are ported yet.
+pub fn render_objects(layer: &ObjectLayer, screens: usize, vertical_level: bool, fg_bg_gfx: u8) -> RenderedLayer {
+    let screens = screens.clamp(1, MAX_SCREEN as usize + 1);
+    let mut tilemap = LevelTilemap::new(screens, vertical_level);
+    let mut unsupported = Vec::new();
+
+    for positioned in layer.positioned_objects(vertical_level) {
+        let (object, (x, y)) = match (positioned.object, positioned.block_pos) {
+            (ObjectInstance::NonExit(object), Some(pos)) => (object, pos),
+            _ => continue,
+        };
+        if !run_object_routine(&mut tilemap, object, (x as usize, y as usize)) {
+            unsupported.push(positioned.index);
+        }
+    }
+
+    if !unsupported.is_empty() {
+        log::warn!(
+            "{} objects couldn't be rendered in tileset {:X} ({:?} object set)",
+            unsupported.len(),
+            fg_bg_gfx,
+            ObjectTileset::from_fg_bg_gfx(fg_bg_gfx)
+        );
+    }
+
+    RenderedLayer { tilemap, unsupported }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Returns whether the object placed its blocks. Only the objects whose blocks are known are
+/// rendered; every other one, including all tileset-specific (0x22-0x3F) and extended objects, is
+/// reported as unsupported.
+fn run_object_routine(tilemap: &mut LevelTilemap, object: &NonExitInstance, pos: (usize, usize)) -> bool {
+    let std_obj_num = object.std_obj_num();
+    if object.ext_obj_num().is_some() || TILESET_SPECIFIC_OBJECTS.contains(&std_obj_num) {
+        return false;
+    }
+
+    // Objects 0x01-0x21 are the same in every tileset.
+    let settings = match ObjectInfo::standard(std_obj_num, ObjectTileset::Normal) {
+        Some(info) => info.settings.decode(object.settings()),
+        None => return false,
+    };
+    match (std_obj_num, settings) {
+        (0x0F, ObjectSettings::TypeAndHeight { variant, height }) => {
+            vertical_pipe(tilemap, pos, variant, height as usize)
+        }
+        (_, ObjectSettings::Size { width, height }) => {
+            match FILL_OBJECT_BLOCKS.iter().find(|(num, _)| *num == std_obj_num) {
+                Some(&(_, block)) => {
+                    tilemap.fill(pos, (width as usize, height as usize), block);
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Pipes two blocks wide. Even types end at the top and odd ones at the bottom; types 2 and 3 can be
+/// entered.
+fn vertical_pipe(tilemap: &mut LevelTilemap, (x, y): (usize, usize), variant: u8, height: usize) -> bool {
+    let end = match variant {
+        0 | 1 => (0x133, 0x134),
+        2 | 3 => (0x137, 0x138),
+        _ => return false,
+    };
+    let end_row = if variant & 1 == 0 { 0 } else { height - 1 };
+    for row in 0..height {
+        let (left, right) = if row == end_row { end } else { (0x135, 0x136) };
+        tilemap.set(x, y + row, left);
+        tilemap.set(x + 1, y + row, right);
+    }
+    true
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Standard objects that fill their whole size with a single block.
+#[rustfmt::skip]
+const FILL_OBJECT_BLOCKS: [(StandardObjectID, Map16BlockId); 7] = [
+    (0x05, 0x02B), // Coins
+    (0x08, 0x114), // Note blocks
+    (0x09, 0x11E), // Turn blocks
+    (0x0B, 0x12E), // Throw blocks
+    (0x0C, 0x12F), // Black piranha plants
+    (0x0D, 0x130), // Cement blocks
+    (0x0E, 0x132), // Brown blocks
+];
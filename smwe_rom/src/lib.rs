@@ -4,11 +4,14 @@ use std::{fs, path::Path};
 
 pub use crate::internal_header::RomInternalHeader;
 use crate::{
-    error::RomParseError,
+    error::{GfxSetError, RomParseError},
     graphics::{
         gfx_file::{GfxFile, GFX_FILES_META},
+        gfx_set::{GfxSets, GfxSlot},
+        map16::Map16Tables,
         palette::ColorPalettes,
     },
+    identification::RomIdentity,
     level::{
         secondary_entrance::{SecondaryEntrance, SECONDARY_ENTRANCE_TABLE},
         Level,
@@ -18,18 +21,24 @@ use crate::{
 };
 
 pub mod compression;
+pub mod crc32;
 pub mod error;
 pub mod graphics;
+pub mod identification;
 pub mod internal_header;
 pub mod level;
+pub mod patch;
 pub mod snes_utils;
 
 pub struct SmwRom {
+    pub identity:            RomIdentity,
     pub internal_header:     RomInternalHeader,
     pub levels:              Vec<Level>,
     pub secondary_entrances: Vec<SecondaryEntrance>,
     pub color_palettes:      ColorPalettes,
     pub gfx_files:           Vec<GfxFile>,
+    pub gfx_sets:            GfxSets,
+    pub map16:               Map16Tables,
 }
 
 impl SmwRom {
@@ -51,6 +60,9 @@ impl SmwRom {
     }
 
     pub fn from_rom(rom: Rom) -> Result<Self, RomParseError> {
+        log::info!("Identifying ROM");
+        let identity = RomIdentity::identify(&rom);
+
         log::info!("Parsing internal ROM header");
         let internal_header = RomInternalHeader::parse(&rom).map_err(RomParseError::InternalHeader)?;
 
@@ -66,7 +78,18 @@ impl SmwRom {
         log::info!("Parsing GFX files");
         let gfx_files = Self::parse_gfx_files(&rom)?;
 
-        Ok(Self { internal_header, levels, secondary_entrances, color_palettes, gfx_files })
+        log::info!("Parsing GFX lists");
+        let gfx_sets = GfxSets::parse(&rom).map_err(RomParseError::GfxSets)?;
+
+        log::info!("Parsing Map16 tables");
+        let map16 = Map16Tables::parse(&rom).map_err(RomParseError::Map16)?;
+
+        Ok(Self { identity, internal_header, levels, secondary_entrances, color_palettes, gfx_files, gfx_sets, map16 })
+    }
+
+    /// GFX files loaded into VRAM for a level, in the order of `GfxSlot::ALL`.
+    pub fn level_gfx_files(&self, level: &Level) -> Result<[(GfxSlot, &GfxFile); 8], GfxSetError> {
+        self.gfx_sets.level_slots(&level.primary_header, &self.gfx_files)
     }
 
     fn parse_levels(rom: &Rom) -> Result<Vec<Level>, RomParseError> {
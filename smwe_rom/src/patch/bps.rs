@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
+
+use num_enum::TryFromPrimitive;
+
+use super::write_changes;
+use crate::{
+    crc32::crc32,
+    error::BpsError,
+    snes_utils::rom::{Rom, MAX_ROM_SIZE},
+};
+
+// BPS patch layout:
+//   "BPS1"
+//   source size, target size, metadata size   - numbers
+//   metadata
+//   actions:
+//     number: ((length - 1) << 2) | action
+//     followed by the action's data (see below)
+//   source CRC32, target CRC32, patch CRC32   - 32-bit little-endian
+// Numbers are variable-length: 7 bits per byte, least significant first, with the top bit set in the last
+// byte. Each byte that's followed by another one also implies +1 to the next one, so that no number has
+// more than one encoding. Signed numbers store the sign in the lowest bit of the magnitude.
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromPrimitive)]
+enum Action {
+    /// Copy bytes from the source at the current output offset
+    SourceRead = 0,
+
+    /// Followed by bytes to copy to the output
+    TargetRead = 1,
+
+    /// Followed by a signed offset to move the source cursor by, then copy bytes from the source
+    SourceCopy = 2,
+
+    /// Followed by a signed offset to move the target cursor by, then copy bytes from the already
+    /// written output
+    TargetCopy = 3,
+}
+
+pub struct BpsPatch<'a> {
+    pub source_size:  usize,
+    pub target_size:  usize,
+    pub metadata:     String,
+    pub source_crc32: u32,
+    pub target_crc32: u32,
+    /// Patch without the checksums at the end.
+    body:             &'a [u8],
+    actions_offset:   usize,
+}
+
+pub fn apply(rom: &mut Rom, patch: &[u8]) -> Result<(), BpsError> {
+    BpsPatch::parse(patch)?.apply(rom)
+}
+
+/// Creates a patch that turns `source` into `target`, using reads from the source at the same offset
+/// where possible, and the longest copies from the source or from the already written part of the target
+/// everywhere else.
+pub fn create(source: &Rom, target: &Rom, metadata: &str) -> Vec<u8> {
+    let source = source.as_bytes();
+    let target = target.as_bytes();
+
+    let mut encoder = Encoder { output: HEADER.to_vec(), source_relative: 0, target_relative: 0 };
+    encoder.write_number(source.len());
+    encoder.write_number(target.len());
+    encoder.write_number(metadata.len());
+    encoder.output.extend_from_slice(metadata.as_bytes());
+
+    let mut source_index = MatchIndex::new(source.len());
+    for position in 0..source.len() {
+        source_index.insert(source, position);
+    }
+    let mut target_index = MatchIndex::new(target.len());
+
+    let mut read_begin = 0;
+    let mut i = 0;
+    while i < target.len() {
+        let rest = &target[i..];
+        let source_read = rest.iter().zip(source.get(i..).unwrap_or(&[])).take_while(|(t, s)| t == s).count();
+        let source_copy = source_index.longest_match(source, rest);
+        let target_copy = target_index.longest_match(target, rest);
+
+        let (action, (position, length)) = [
+            (Action::SourceRead, (i, source_read)),
+            (Action::SourceCopy, source_copy),
+            (Action::TargetCopy, target_copy),
+        ]
+        .iter()
+        .copied()
+        .fold(
+            (Action::TargetRead, (0, 0)),
+            |best, candidate| if candidate.1 .1 > best.1 .1 { candidate } else { best },
+        );
+
+        if length >= MIN_MATCH_LENGTH {
+            encoder.write_target_read(&target[read_begin..i]);
+            encoder.write_match(action, position, length);
+            for position in i..i + length {
+                target_index.insert(target, position);
+            }
+            i += length;
+            read_begin = i;
+        } else {
+            target_index.insert(target, i);
+            i += 1;
+        }
+    }
+    encoder.write_target_read(&target[read_begin..]);
+
+    let mut output = encoder.output;
+    output.extend_from_slice(&crc32(source).to_le_bytes());
+    output.extend_from_slice(&crc32(target).to_le_bytes());
+    output.extend_from_slice(&crc32(&output).to_le_bytes());
+    output
+}
+
+impl<'a> BpsPatch<'a> {
+    /// Reads the patch's header and verifies its checksum.
+    pub fn parse(patch: &'a [u8]) -> Result<Self, BpsError> {
+        if !patch.starts_with(HEADER) {
+            return Err(BpsError::MissingHeader);
+        }
+        if patch.len() < HEADER.len() + FOOTER_SIZE {
+            return Err(BpsError::UnexpectedEnd(patch.len()));
+        }
+
+        let body_size = patch.len() - FOOTER_SIZE;
+        let read_u32 = |offset: usize| u32::from_le_bytes(patch[offset..offset + 4].try_into().unwrap());
+        let expected = read_u32(body_size + 8);
+        let actual = crc32(&patch[..body_size + 8]);
+        if expected != actual {
+            return Err(BpsError::PatchChecksum { expected, actual });
+        }
+
+        let mut reader = PatchReader { patch: &patch[..body_size], offset: HEADER.len() };
+        let source_size = reader.read_number()?;
+        let target_size = reader.read_number()?;
+        let metadata_size = reader.read_number()?;
+        let metadata = String::from_utf8_lossy(reader.read_bytes(metadata_size)?).into_owned();
+
+        Ok(Self {
+            source_size,
+            target_size,
+            metadata,
+            source_crc32: read_u32(body_size),
+            target_crc32: read_u32(body_size + 4),
+            body: &patch[..body_size],
+            actions_offset: reader.offset,
+        })
+    }
+
+    /// Applies the patch after checking that `rom` is the ROM it was made for, and that the result
+    /// matches the target checksum. The ROM is left untouched if any of these fail.
+    pub fn apply(&self, rom: &mut Rom) -> Result<(), BpsError> {
+        let source = rom.as_bytes();
+        let actual = crc32(source);
+        if source.len() != self.source_size || actual != self.source_crc32 {
+            return Err(BpsError::WrongSourceRom { expected: self.source_crc32, actual });
+        }
+
+        if self.target_size > MAX_ROM_SIZE {
+            return Err(BpsError::TargetTooLarge(self.target_size));
+        }
+
+        let mut target = Vec::with_capacity(self.target_size);
+        let mut reader = PatchReader { patch: self.body, offset: self.actions_offset };
+        let mut source_relative = 0;
+        let mut target_relative = 0;
+        while reader.offset < self.body.len() {
+            let action_offset = reader.offset;
+            let invalid = || BpsError::InvalidAction(action_offset);
+
+            let data = reader.read_number()?;
+            let length = (data >> 2) + 1;
+            if target.len() + length > self.target_size {
+                return Err(invalid());
+            }
+            let action = Action::try_from((data & 0b11) as u8).expect("Every 2-bit value is an action");
+            match action {
+                Action::SourceRead => {
+                    let bytes = source.get(target.len()..target.len() + length).ok_or_else(invalid)?;
+                    target.extend_from_slice(bytes);
+                }
+                Action::TargetRead => {
+                    let bytes = reader.read_bytes(length)?;
+                    target.extend_from_slice(bytes);
+                }
+                Action::SourceCopy => {
+                    source_relative = reader.read_offset(source_relative).ok_or_else(invalid)?;
+                    let bytes = source.get(source_relative..source_relative + length).ok_or_else(invalid)?;
+                    target.extend_from_slice(bytes);
+                    source_relative += length;
+                }
+                Action::TargetCopy => {
+                    target_relative = reader.read_offset(target_relative).ok_or_else(invalid)?;
+                    if target_relative >= target.len() {
+                        return Err(invalid());
+                    }
+                    for _ in 0..length {
+                        target.push(target[target_relative]);
+                        target_relative += 1;
+                    }
+                }
+            }
+        }
+
+        if target.len() != self.target_size {
+            return Err(BpsError::TargetSize { expected: self.target_size, actual: target.len() });
+        }
+        let actual = crc32(&target);
+        if actual != self.target_crc32 {
+            return Err(BpsError::TargetChecksum { expected: self.target_crc32, actual });
+        }
+
+        write_changes(rom, &target).map_err(BpsError::Write)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+const HEADER: &[u8] = b"BPS1";
+const FOOTER_SIZE: usize = 12;
+const MIN_MATCH_LENGTH: usize = 4;
+const MAX_MATCH_CANDIDATES: usize = 32;
+
+struct PatchReader<'a> {
+    patch:  &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], BpsError> {
+        let end = self.offset.checked_add(count).ok_or(BpsError::UnexpectedEnd(self.patch.len()))?;
+        let bytes = self.patch.get(self.offset..end).ok_or(BpsError::UnexpectedEnd(self.patch.len()))?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_number(&mut self) -> Result<usize, BpsError> {
+        let begin = self.offset;
+        let overflow = || BpsError::InvalidNumber(begin);
+        let mut number = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.read_bytes(1)?[0];
+            let value = ((byte & 0x7F) as usize).checked_mul(shift).ok_or_else(overflow)?;
+            number = number.checked_add(value).ok_or_else(overflow)?;
+            if (byte & 0x80) != 0 {
+                return Ok(number);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(overflow)?;
+            number = number.checked_add(shift).ok_or_else(overflow)?;
+        }
+    }
+
+    /// Reads a signed number and moves `position` by it. Returns `None` if it goes out of bounds.
+    fn read_offset(&mut self, position: usize) -> Option<usize> {
+        let data = self.read_number().ok()?;
+        if (data & 1) != 0 {
+            position.checked_sub(data >> 1)
+        } else {
+            position.checked_add(data >> 1)
+        }
+    }
+}
+
+struct Encoder {
+    output:          Vec<u8>,
+    source_relative: usize,
+    target_relative: usize,
+}
+
+impl Encoder {
+    fn write_number(&mut self, mut number: usize) {
+        loop {
+            let byte = (number & 0x7F) as u8;
+            number >>= 7;
+            if number == 0 {
+                self.output.push(0x80 | byte);
+                break;
+            }
+            self.output.push(byte);
+            number -= 1;
+        }
+    }
+
+    fn write_action(&mut self, action: Action, length: usize) {
+        self.write_number(((length - 1) << 2) | action as usize);
+    }
+
+    fn write_target_read(&mut self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.write_action(Action::TargetRead, bytes.len());
+            self.output.extend_from_slice(bytes);
+        }
+    }
+
+    fn write_match(&mut self, action: Action, position: usize, length: usize) {
+        self.write_action(action, length);
+        let relative = match action {
+            Action::SourceCopy => &mut self.source_relative,
+            Action::TargetCopy => &mut self.target_relative,
+            _ => return,
+        };
+        let offset =
+            if position >= *relative { (position - *relative) << 1 } else { ((*relative - position) << 1) | 1 };
+        *relative = position + length;
+        self.write_number(offset);
+    }
+}
+
+/// Hash chains of the positions of every `MIN_MATCH_LENGTH`-byte sequence inserted so far.
+struct MatchIndex {
+    heads: HashMap<[u8; MIN_MATCH_LENGTH], usize>,
+    prev:  Vec<usize>,
+}
+
+impl MatchIndex {
+    fn new(size: usize) -> Self {
+        Self { heads: HashMap::new(), prev: vec![usize::MAX; size] }
+    }
+
+    fn key(data: &[u8]) -> Option<[u8; MIN_MATCH_LENGTH]> {
+        data.get(..MIN_MATCH_LENGTH).map(|key| key.try_into().unwrap())
+    }
+
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if let Some(key) = Self::key(&data[position..]) {
+            if let Some(head) = self.heads.insert(key, position) {
+                self.prev[position] = head;
+            }
+        }
+    }
+
+    /// Finds the position and length of the longest prefix of `needle` found at one of the indexed
+    /// positions of `data`.
+    fn longest_match(&self, data: &[u8], needle: &[u8]) -> (usize, usize) {
+        let mut best = (0, 0);
+        let mut candidate = Self::key(needle).and_then(|key| self.heads.get(&key)).copied().unwrap_or(usize::MAX);
+        let mut candidates_left = MAX_MATCH_CANDIDATES;
+        while candidate != usize::MAX && candidates_left > 0 {
+            let length = data[candidate..].iter().zip(needle).take_while(|(a, b)| a == b).count();
+            if length > best.1 {
+                best = (candidate, length);
+                if length == needle.len() {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+            candidates_left -= 1;
+        }
+        best
+    }
+}
@@ -0,0 +1,228 @@
+use super::write_changes;
+use crate::{
+    error::IpsError,
+    snes_utils::rom::{Rom, SMC_HEADER_SIZE},
+};
+
+// IPS patch layout:
+//   "PATCH"
+//   records:
+//     OOOOOO LLLL DD...      - plain: L bytes of data written at offset O
+//     OOOOOO 0000 RRRR BB    - RLE: byte B repeated R times starting at offset O
+//   "EOF"
+//   TTTTTT                   - optional: size to truncate the patched file to
+// All numbers are big-endian. Offsets refer to the patched file, see `Offsets`.
+
+/// Offsets are 24-bit, so nothing past the first 16 MiB can be patched.
+pub const MAX_SIZE: usize = 0x1000000;
+
+/// What the offsets of a patch's records count from. Most patches for SMW are made against a file with
+/// an SMC header, so their offsets are 0x200 bytes past the ROM data they change.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Offsets {
+    /// Offsets count from the start of the ROM data.
+    Headerless,
+    /// Offsets count from the start of an SMC header in front of the ROM data.
+    Headered,
+}
+
+const HEADER: &[u8] = b"PATCH";
+const FOOTER: &[u8] = b"EOF";
+/// Record offset that would be read as the footer.
+const FOOTER_OFFSET: usize = 0x454F46;
+const MAX_RECORD_LENGTH: usize = 0xFFFF;
+const RECORD_HEADER_SIZE: usize = 5;
+const RLE_RECORD_SIZE: usize = 8;
+
+/// Applies the patch to a copy of the ROM, which replaces the ROM only if the whole patch applies.
+/// With `Offsets::Headered`, records changing the SMC header update the ROM's header if it has one.
+pub fn apply(rom: &mut Rom, patch: &[u8], offsets: Offsets) -> Result<(), IpsError> {
+    let header_size = match offsets {
+        Offsets::Headerless => 0,
+        Offsets::Headered => SMC_HEADER_SIZE,
+    };
+    let mut file = rom.to_bytes(offsets == Offsets::Headered);
+    apply_records(&mut file, patch)?;
+    if file.len() < header_size {
+        return Err(IpsError::TooSmall(file.len()));
+    }
+
+    if let Some(smc_header) = rom.smc_header_mut().filter(|_| offsets == Offsets::Headered) {
+        smc_header.copy_from_slice(&file[..SMC_HEADER_SIZE]);
+    }
+    write_changes(rom, &file[header_size..]).map_err(IpsError::Write)
+}
+
+/// Creates a patch that turns `source` into `target`. Changes separated by only a few unchanged bytes
+/// are put in a single record, and long runs of the same byte are written as RLE records.
+pub fn create(source: &Rom, target: &Rom) -> Result<Vec<u8>, IpsError> {
+    let source = source.as_bytes();
+    let target = target.as_bytes();
+    if target.len() > MAX_SIZE {
+        return Err(IpsError::TooLarge(target.len()));
+    }
+
+    let mut writer = PatchWriter { target, output: HEADER.to_vec() };
+    let differs = |i: usize| source.get(i) != Some(&target[i]);
+    let mut i = 0;
+    while i < target.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+
+        let begin = i;
+        let mut end = i + 1;
+        while end < target.len() {
+            if differs(end) {
+                end += 1;
+            } else {
+                let gap = (end..target.len()).take_while(|&k| !differs(k)).count();
+                if gap <= RECORD_HEADER_SIZE && end + gap < target.len() {
+                    end += gap;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        writer.write_region(begin, end);
+        i = end;
+    }
+
+    writer.output.extend_from_slice(FOOTER);
+    if target.len() < source.len() {
+        writer.output.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(writer.output)
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn apply_records(file: &mut Vec<u8>, patch: &[u8]) -> Result<(), IpsError> {
+    let mut reader = PatchReader { patch, offset: 0 };
+    if reader.read_bytes(HEADER.len()).ok() != Some(HEADER) {
+        return Err(IpsError::MissingHeader);
+    }
+
+    loop {
+        let offset = reader.read_u24()?;
+        if offset == FOOTER_OFFSET {
+            break;
+        }
+        let length = reader.read_u16()?;
+        if length == 0 {
+            let count = reader.read_u16()?;
+            let byte = reader.read_u8()?;
+            write(file, offset, &vec![byte; count])?;
+        } else {
+            let bytes = reader.read_bytes(length)?;
+            write(file, offset, bytes)?;
+        }
+    }
+
+    match patch.len() - reader.offset {
+        0 => Ok(()),
+        3 => {
+            let size = reader.read_u24()?;
+            file.truncate(size);
+            Ok(())
+        }
+        _ => Err(IpsError::TrailingData(reader.offset)),
+    }
+}
+
+fn write(file: &mut Vec<u8>, offset: usize, bytes: &[u8]) -> Result<(), IpsError> {
+    let end = offset + bytes.len();
+    if end > MAX_SIZE {
+        return Err(IpsError::TooLarge(end));
+    }
+    if end > file.len() {
+        file.resize(end, 0);
+    }
+    file[offset..end].copy_from_slice(bytes);
+    Ok(())
+}
+
+struct PatchReader<'a> {
+    patch:  &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], IpsError> {
+        let bytes = self.patch.get(self.offset..self.offset + count).ok_or(IpsError::UnexpectedEnd(self.offset))?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, IpsError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<usize, IpsError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(((bytes[0] as usize) << 8) | bytes[1] as usize)
+    }
+
+    fn read_u24(&mut self) -> Result<usize, IpsError> {
+        let bytes = self.read_bytes(3)?;
+        Ok(((bytes[0] as usize) << 16) | ((bytes[1] as usize) << 8) | bytes[2] as usize)
+    }
+}
+
+struct PatchWriter<'a> {
+    target: &'a [u8],
+    output: Vec<u8>,
+}
+
+impl PatchWriter<'_> {
+    /// Splits the region into plain and RLE records. A run of equal bytes is only worth an RLE record
+    /// if it's longer than the RLE record itself, plus the header of the plain record that follows it.
+    fn write_region(&mut self, begin: usize, end: usize) {
+        let mut plain_begin = begin;
+        let mut i = begin;
+        while i < end {
+            let byte = self.target[i];
+            let run = self.target[i..end].iter().take(MAX_RECORD_LENGTH).take_while(|&&b| b == byte).count();
+            let min_run = if i + run == end { RLE_RECORD_SIZE } else { RLE_RECORD_SIZE + RECORD_HEADER_SIZE };
+            if run > min_run {
+                self.write_plain(plain_begin, i);
+                self.write_rle(i, run);
+                plain_begin = i + run;
+            }
+            i += run;
+        }
+        self.write_plain(plain_begin, end);
+    }
+
+    fn write_plain(&mut self, mut begin: usize, end: usize) {
+        while begin < end {
+            if begin == FOOTER_OFFSET {
+                begin -= 1;
+            }
+            let length = (end - begin).min(MAX_RECORD_LENGTH);
+            self.write_record_header(begin, length);
+            self.output.extend_from_slice(&self.target[begin..begin + length]);
+            begin += length;
+        }
+    }
+
+    fn write_rle(&mut self, mut begin: usize, mut count: usize) {
+        if begin == FOOTER_OFFSET {
+            self.write_plain(begin, begin + 1);
+            begin += 1;
+            count -= 1;
+        }
+        if count > 0 {
+            self.write_record_header(begin, 0);
+            self.output.extend_from_slice(&(count as u16).to_be_bytes());
+            self.output.push(self.target[begin]);
+        }
+    }
+
+    fn write_record_header(&mut self, offset: usize, length: usize) {
+        self.output.extend_from_slice(&(offset as u32).to_be_bytes()[1..]);
+        self.output.extend_from_slice(&(length as u16).to_be_bytes());
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{
+    error::RomError,
+    snes_utils::{addr::AddrPc, rom::Rom},
+};
+
+pub mod bps;
+pub mod ips;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Writes only the bytes that differ, so that the ROM's modified regions reflect what the patch changed.
+fn write_changes(rom: &mut Rom, target: &[u8]) -> Result<(), RomError> {
+    if rom.len() != target.len() {
+        rom.resize(target.len(), 0);
+    }
+    let mut i = 0;
+    while i < target.len() {
+        let begin = i;
+        while i < target.len() && rom.as_bytes()[i] != target[i] {
+            i += 1;
+        }
+        if begin < i {
+            rom.write_pc(AddrPc(begin), &target[begin..i])?;
+        } else {
+            i += 1;
+        }
+    }
+    Ok(())
+}
@@ -50,12 +50,49 @@ pub mod types {
         type OppositeAddr: Addr;
         fn try_from_lorom(addr: Self::OppositeAddr) -> Result<Self, AddressError>;
         fn try_from_hirom(addr: Self::OppositeAddr) -> Result<Self, AddressError>;
+        fn try_from_exlorom(addr: Self::OppositeAddr) -> Result<Self, AddressError>;
+        fn try_from_exhirom(addr: Self::OppositeAddr) -> Result<Self, AddressError>;
+        fn try_from_sa1(addr: Self::OppositeAddr, banks: Sa1BankMapping) -> Result<Self, AddressError>;
         fn is_valid_lorom(&self) -> bool;
         fn is_valid_hirom(&self) -> bool;
+        fn is_valid_exlorom(&self) -> bool;
+        fn is_valid_exhirom(&self) -> bool;
+        fn is_valid_sa1(&self, banks: Sa1BankMapping) -> bool;
     }
 
     pub type Mask = usize;
 
+    /// Values of the SA-1 Super MMC registers $2220-$2223 (CXB, DXB, EXB and FXB). Bits 0-2 of each of
+    /// them select the 1 MB block of ROM mapped to banks $C0-$CF, $D0-$DF, $E0-$EF and $F0-$FF
+    /// respectively. Banks $00-$1F, $20-$3F, $80-$9F and $A0-$BF follow the same block if bit 7 is set,
+    /// otherwise they keep showing blocks 0, 1, 2 and 3.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub struct Sa1BankMapping(pub [u8; 4]);
+
+    impl Default for Sa1BankMapping {
+        fn default() -> Self {
+            Self([0, 1, 2, 3])
+        }
+    }
+
+    impl Sa1BankMapping {
+        const LOROM_AREA_BANKS: [usize; 4] = [0x00, 0x20, 0x80, 0xA0];
+
+        /// Block mapped to the HiROM area banks of a register.
+        fn block(&self, register: usize) -> usize {
+            (self.0[register] & 0b111) as usize
+        }
+
+        /// Block mapped to the LoROM area banks of a register.
+        fn lorom_block(&self, register: usize) -> usize {
+            if (self.0[register] & 0x80) != 0 {
+                self.block(register)
+            } else {
+                register
+            }
+        }
+    }
+
     macro_rules! gen_address_type {
         ($name:ident) => {
             #[derive(Copy, Clone, Debug)]
@@ -137,6 +174,37 @@ pub mod types {
             }
         }
 
+        fn try_from_exlorom(addr: AddrSnes) -> Result<Self, AddressError> {
+            if addr.is_valid_exlorom() {
+                Ok(Self((((addr.0 ^ 0x800000) & 0xFF0000) >> 1) | (addr.0 & 0x7FFF)))
+            } else {
+                Err(AddressError::InvalidSnesExLoRom(addr))
+            }
+        }
+
+        fn try_from_exhirom(addr: AddrSnes) -> Result<Self, AddressError> {
+            if addr.is_valid_exhirom() {
+                Ok(Self((addr.0 & 0x3FFFFF) | if (addr.0 & 0x800000) == 0 { 0x400000 } else { 0 }))
+            } else {
+                Err(AddressError::InvalidSnesExHiRom(addr))
+            }
+        }
+
+        fn try_from_sa1(addr: AddrSnes, banks: Sa1BankMapping) -> Result<Self, AddressError> {
+            if addr.is_valid_sa1(banks) {
+                let bank = addr.0 >> 16;
+                if bank >= 0xC0 {
+                    let block = banks.block((bank - 0xC0) >> 4);
+                    Ok(Self((block << 20) | (addr.0 & 0x0FFFFF)))
+                } else {
+                    let block = banks.lorom_block(((bank & 0x80) >> 6) | ((bank & 0x20) >> 5));
+                    Ok(Self((block << 20) | ((bank & 0x1F) << 15) | (addr.0 & 0x7FFF)))
+                }
+            } else {
+                Err(AddressError::InvalidSnesSa1(addr))
+            }
+        }
+
         fn is_valid_lorom(&self) -> bool {
             self.0 < 0x400000
         }
@@ -144,6 +212,18 @@ pub mod types {
         fn is_valid_hirom(&self) -> bool {
             self.0 < 0x400000
         }
+
+        fn is_valid_exlorom(&self) -> bool {
+            self.0 < 0x7F0000
+        }
+
+        fn is_valid_exhirom(&self) -> bool {
+            self.0 < 0x7E0000
+        }
+
+        fn is_valid_sa1(&self, banks: Sa1BankMapping) -> bool {
+            (0..4).any(|register| banks.block(register) == self.0 >> 20 || banks.lorom_block(register) == self.0 >> 20)
+        }
     }
 
     impl TryFrom<AddrSnes> for AddrPc {
@@ -185,6 +265,39 @@ pub mod types {
             }
         }
 
+        fn try_from_exlorom(addr: AddrPc) -> Result<Self, AddressError> {
+            if addr.is_valid_exlorom() {
+                let upper_half = if addr.0 < 0x400000 { 0x800000 } else { 0 };
+                Ok(Self(((addr.0 << 1) & 0x7F0000) | (addr.0 & 0x7FFF) | 0x8000 | upper_half))
+            } else {
+                Err(AddressError::InvalidPcExLoRom(addr))
+            }
+        }
+
+        fn try_from_exhirom(addr: AddrPc) -> Result<Self, AddressError> {
+            if addr.is_valid_exhirom() {
+                if addr.0 < 0x400000 {
+                    Ok(Self(addr.0 | 0xC00000))
+                } else {
+                    Ok(Self(addr.0))
+                }
+            } else {
+                Err(AddressError::InvalidPcExHiRom(addr))
+            }
+        }
+
+        fn try_from_sa1(addr: AddrPc, banks: Sa1BankMapping) -> Result<Self, AddressError> {
+            let block = addr.0 >> 20;
+            if let Some(register) = (0..4).find(|&register| banks.lorom_block(register) == block) {
+                let bank = Sa1BankMapping::LOROM_AREA_BANKS[register] | ((addr.0 & 0x0F8000) >> 15);
+                Ok(Self((bank << 16) | (addr.0 & 0x7FFF) | 0x8000))
+            } else if let Some(register) = (0..4).find(|&register| banks.block(register) == block) {
+                Ok(Self(((0xC0 | (register << 4)) << 16) | (addr.0 & 0x0FFFFF)))
+            } else {
+                Err(AddressError::InvalidPcSa1(addr))
+            }
+        }
+
         fn is_valid_lorom(&self) -> bool {
             let wram = (self.0 & 0xFE0000) == 0x7E0000;
             let junk = (self.0 & 0x408000) == 0x000000;
@@ -197,6 +310,28 @@ pub mod types {
             let junk = (self.0 & 0x408000) == 0x000000;
             !wram && !junk
         }
+
+        fn is_valid_exlorom(&self) -> bool {
+            let wram = (self.0 & 0xFE0000) == 0x7E0000;
+            let junk = (self.0 & 0x408000) == 0x000000;
+            let sram = (self.0 & 0xF08000) == 0x700000;
+            let beyond = self.0 > 0xFFFFFF;
+            !wram && !junk && !sram && !beyond
+        }
+
+        fn is_valid_exhirom(&self) -> bool {
+            let wram = (self.0 & 0xFE0000) == 0x7E0000;
+            let junk = (self.0 & 0x408000) == 0x000000;
+            let beyond = self.0 > 0xFFFFFF;
+            !wram && !junk && !beyond
+        }
+
+        fn is_valid_sa1(&self, _banks: Sa1BankMapping) -> bool {
+            let bank = self.0 >> 16;
+            let rom_hirom = (0xC0..=0xFF).contains(&bank);
+            let rom_lorom = (bank & 0x40) == 0 && (self.0 & 0x8000) != 0 && bank <= 0xBF;
+            rom_hirom || rom_lorom
+        }
     }
 
     impl TryFrom<AddrPc> for AddrSnes {
@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, convert::TryInto};
+
+use crate::{
+    error::FreeSpaceError,
+    snes_utils::{
+        addr::AddrPc,
+        mapper::Mapper,
+        rom::{Rom, EXLOROM_MIRROR},
+        rom_slice::PcSlice,
+    },
+};
+
+// A RATS tag protects the data that follows it from being treated as free space:
+//   "STAR" SSSS CCCC
+//   SSSS: size of the protected data minus 1, little-endian
+//   CCCC: SSSS ^ 0xFFFF
+
+pub const RATS_TAG_SIZE: usize = 8;
+pub const RATS_MAX_DATA_SIZE: usize = 0x10000;
+/// Everything before the end of the original SMW ROM is considered used.
+pub const DEFAULT_SEARCH_START: AddrPc = AddrPc(0x80000);
+
+const RATS_MAGIC: &[u8] = b"STAR";
+
+#[derive(Copy, Clone, Debug)]
+pub struct RatsTag {
+    /// Location of the tag itself.
+    pub begin:     AddrPc,
+    /// Size of the protected data following the tag.
+    pub data_size: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BankUsage {
+    pub begin: AddrPc,
+    pub size:  usize,
+    pub free:  usize,
+}
+
+pub struct FreeSpace {
+    /// Sorted, non-overlapping `begin => end` ranges of free bytes, as PC offsets.
+    free:      BTreeMap<usize, usize>,
+    bank_size: usize,
+    rom_size:  usize,
+    free_byte: u8,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl RatsTag {
+    pub fn read(rom: &Rom, begin: AddrPc) -> Option<Self> {
+        let bytes = rom.slice_pc(PcSlice::new(begin, RATS_TAG_SIZE)).ok()?;
+        let size = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
+        let complement = u16::from_le_bytes(bytes[6..8].try_into().unwrap());
+        if &bytes[..4] == RATS_MAGIC && size ^ complement == 0xFFFF {
+            Some(Self { begin, data_size: size as usize + 1 })
+        } else {
+            None
+        }
+    }
+
+    pub fn to_bytes(data_size: usize) -> [u8; RATS_TAG_SIZE] {
+        let size = (data_size - 1) as u16;
+        let [s_lo, s_hi] = size.to_le_bytes();
+        let [c_lo, c_hi] = (size ^ 0xFFFF).to_le_bytes();
+        [b'S', b'T', b'A', b'R', s_lo, s_hi, c_lo, c_hi]
+    }
+
+    pub fn data_begin(&self) -> AddrPc {
+        self.begin + RATS_TAG_SIZE
+    }
+
+    /// The tag together with the data it protects.
+    pub fn block(&self) -> PcSlice {
+        PcSlice::new(self.begin, RATS_TAG_SIZE + self.data_size)
+    }
+}
+
+impl BankUsage {
+    pub fn used(&self) -> usize {
+        self.size - self.free
+    }
+}
+
+impl FreeSpace {
+    pub fn scan(rom: &Rom) -> Self {
+        Self::scan_from(rom, DEFAULT_SEARCH_START, 0x00)
+    }
+
+    /// Finds all runs of `free_byte` starting from `search_start`, skipping over data protected by
+    /// RATS tags and the ExLoROM mirror of the original game.
+    pub fn scan_from(rom: &Rom, search_start: AddrPc, free_byte: u8) -> Self {
+        let data = rom.as_bytes();
+        let mut free = BTreeMap::new();
+        let mut i = search_start.0;
+        while i < data.len() {
+            if let Some(tag) = RatsTag::read(rom, AddrPc(i)) {
+                i += tag.block().size;
+            } else if data[i] == free_byte {
+                let begin = i;
+                while i < data.len() && data[i] == free_byte {
+                    i += 1;
+                }
+                free.insert(begin, i);
+            } else {
+                i += 1;
+            }
+        }
+        log::info!(
+            "Found {:#X} bytes of free space in {} blocks",
+            free.iter().map(|(b, e)| e - b).sum::<usize>(),
+            free.len()
+        );
+        let mut free_space = Self { free, bank_size: rom.mapper().bank_size(), rom_size: data.len(), free_byte };
+        if rom.mapper() == Mapper::ExLoRom {
+            free_space.protect(EXLOROM_MIRROR);
+        }
+        free_space
+    }
+
+    /// Marks the region as used, so that nothing gets allocated in it.
+    pub fn protect(&mut self, region: PcSlice) {
+        self.remove(region.begin.0, region.begin.0 + region.size);
+    }
+
+    /// Writes `data` preceded by a RATS tag into the first free block that fits it without crossing
+    /// a bank boundary, and returns the location of the data.
+    pub fn allocate(&mut self, rom: &mut Rom, data: &[u8]) -> Result<AddrPc, FreeSpaceError> {
+        self.allocate_in(rom, data, PcSlice::new(AddrPc(0), self.rom_size))
+    }
+
+    /// Same as `allocate`, but only uses free space within `region`, for data that has to be placed
+    /// in a specific bank.
+    pub fn allocate_in(&mut self, rom: &mut Rom, data: &[u8], region: PcSlice) -> Result<AddrPc, FreeSpaceError> {
+        if data.is_empty() || data.len() > RATS_MAX_DATA_SIZE {
+            return Err(FreeSpaceError::InvalidSize(data.len()));
+        }
+        let needed = RATS_TAG_SIZE + data.len();
+        let begin = self.find_fitting(needed, region).ok_or(FreeSpaceError::NoSpace(data.len()))?;
+
+        rom.write_pc(AddrPc(begin), &RatsTag::to_bytes(data.len())).map_err(FreeSpaceError::Write)?;
+        rom.write_pc(AddrPc(begin + RATS_TAG_SIZE), data).map_err(FreeSpaceError::Write)?;
+        self.remove(begin, begin + needed);
+        Ok(AddrPc(begin + RATS_TAG_SIZE))
+    }
+
+    /// Clears the RATS tag preceding `data_begin` and the data it protects, and makes it free space.
+    pub fn free(&mut self, rom: &mut Rom, data_begin: AddrPc) -> Result<(), FreeSpaceError> {
+        let tag = data_begin
+            .0
+            .checked_sub(RATS_TAG_SIZE)
+            .and_then(|begin| RatsTag::read(rom, AddrPc(begin)))
+            .ok_or(FreeSpaceError::NoTag(data_begin))?;
+        let block = tag.block();
+        let cleared = rom.slice_pc_mut(block).map_err(FreeSpaceError::Write)?;
+        cleared.iter_mut().for_each(|b| *b = self.free_byte);
+        self.insert(block.begin.0, block.begin.0 + block.size);
+        Ok(())
+    }
+
+    pub fn total_free(&self) -> usize {
+        self.free.iter().map(|(b, e)| e - b).sum()
+    }
+
+    /// Size of the largest block that can be allocated, including its RATS tag.
+    pub fn largest_free_block(&self) -> usize {
+        self.free.iter().flat_map(|(&b, &e)| self.split_by_banks(b, e)).map(|(b, e)| e - b).max().unwrap_or(0)
+    }
+
+    /// How much smaller the largest free block is than it could be at best: a whole bank, or all of
+    /// the free space if there's less than that. Ranges from 0 to almost 1 when the free space is split
+    /// into many small blocks.
+    pub fn fragmentation(&self) -> f64 {
+        let best = self.total_free().min(self.bank_size);
+        if best == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free_block() as f64 / best as f64
+        }
+    }
+
+    pub fn bank_usage(&self) -> Vec<BankUsage> {
+        let mut banks: Vec<BankUsage> = (0..self.rom_size)
+            .step_by(self.bank_size)
+            .map(|begin| BankUsage { begin: AddrPc(begin), size: self.bank_size.min(self.rom_size - begin), free: 0 })
+            .collect();
+        for (begin, end) in self.free.iter().flat_map(|(&b, &e)| self.split_by_banks(b, e)) {
+            banks[begin / self.bank_size].free += end - begin;
+        }
+        banks
+    }
+
+    pub fn free_blocks(&self) -> Vec<PcSlice> {
+        self.free.iter().map(|(&b, &e)| PcSlice::new(AddrPc(b), e - b)).collect()
+    }
+
+    fn split_by_banks(&self, mut begin: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut parts = Vec::new();
+        while begin < end {
+            let bank_end = (begin / self.bank_size + 1) * self.bank_size;
+            parts.push((begin, bank_end.min(end)));
+            begin = bank_end;
+        }
+        parts
+    }
+
+    fn find_fitting(&self, size: usize, region: PcSlice) -> Option<usize> {
+        let (region_begin, region_end) = (region.begin.0, region.begin.0 + region.size);
+        self.free
+            .iter()
+            .flat_map(|(&b, &e)| self.split_by_banks(b, e))
+            .map(|(b, e)| (b.max(region_begin), e.min(region_end)))
+            .find(|&(b, e)| b < e && e - b >= size)
+            .map(|(b, _)| b)
+    }
+
+    fn insert(&mut self, mut begin: usize, mut end: usize) {
+        let touching: Vec<(usize, usize)> =
+            self.free.range(..=end).rev().take_while(|(_, &e)| e >= begin).map(|(&b, &e)| (b, e)).collect();
+        for (b, e) in touching {
+            self.free.remove(&b);
+            begin = begin.min(b);
+            end = end.max(e);
+        }
+        self.free.insert(begin, end);
+    }
+
+    fn remove(&mut self, begin: usize, end: usize) {
+        let overlapping: Vec<(usize, usize)> =
+            self.free.range(..end).rev().take_while(|(_, &e)| e > begin).map(|(&b, &e)| (b, e)).collect();
+        for (b, e) in overlapping {
+            self.free.remove(&b);
+            if b < begin {
+                self.free.insert(b, begin);
+            }
+            if e > end {
+                self.free.insert(end, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::{
+    error::AddressError,
+    internal_header::{MapMode, RomInternalHeader},
+    snes_utils::addr::{Addr, AddrPc, AddrSnes, Sa1BankMapping},
+};
+
+/// Memory map used to translate between SNES addresses and offsets in the ROM file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mapper {
+    LoRom,
+    HiRom,
+    ExLoRom,
+    ExHiRom,
+    Sa1(Sa1BankMapping),
+}
+
+impl Mapper {
+    pub fn from_header(header: &RomInternalHeader) -> Self {
+        use MapMode::*;
+        if header.map_mode.is_sa1() || header.rom_type.has_sa1() {
+            Mapper::Sa1(Sa1BankMapping::default())
+        } else {
+            match header.map_mode {
+                SlowLoRom | FastLoRom | Sa1 => Mapper::LoRom,
+                SlowHiRom | FastHiRom => Mapper::HiRom,
+                SlowExLoRom | FastExLoRom => Mapper::ExLoRom,
+                SlowExHiRom | FastExHiRom => Mapper::ExHiRom,
+            }
+        }
+    }
+
+    /// Size of the contiguous part of the ROM mapped to a single SNES bank.
+    pub fn bank_size(self) -> usize {
+        match self {
+            Mapper::LoRom | Mapper::ExLoRom | Mapper::Sa1(_) => 0x8000,
+            Mapper::HiRom | Mapper::ExHiRom => 0x10000,
+        }
+    }
+
+    pub fn to_pc(self, addr: AddrSnes) -> Result<AddrPc, AddressError> {
+        match self {
+            Mapper::LoRom => AddrPc::try_from_lorom(addr),
+            Mapper::HiRom => AddrPc::try_from_hirom(addr),
+            Mapper::ExLoRom => AddrPc::try_from_exlorom(addr),
+            Mapper::ExHiRom => AddrPc::try_from_exhirom(addr),
+            Mapper::Sa1(banks) => AddrPc::try_from_sa1(addr, banks),
+        }
+    }
+
+    pub fn to_snes(self, addr: AddrPc) -> Result<AddrSnes, AddressError> {
+        match self {
+            Mapper::LoRom => AddrSnes::try_from_lorom(addr),
+            Mapper::HiRom => AddrSnes::try_from_hirom(addr),
+            Mapper::ExLoRom => AddrSnes::try_from_exlorom(addr),
+            Mapper::ExHiRom => AddrSnes::try_from_exhirom(addr),
+            Mapper::Sa1(banks) => AddrSnes::try_from_sa1(addr, banks),
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for Mapper {
+    fn default() -> Self {
+        Mapper::LoRom
+    }
+}
+
+impl fmt::Display for Mapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Mapper::LoRom => "LoROM",
+            Mapper::HiRom => "HiROM",
+            Mapper::ExLoRom => "ExLoROM",
+            Mapper::ExHiRom => "ExHiROM",
+            Mapper::Sa1(_) => "SA-1",
+        })
+    }
+}
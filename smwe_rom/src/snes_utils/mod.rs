@@ -1,3 +1,5 @@
 pub mod addr;
+pub mod free_space;
+pub mod mapper;
 pub mod rom;
 pub mod rom_slice;
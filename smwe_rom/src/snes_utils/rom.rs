@@ -1,42 +1,120 @@
+use std::collections::BTreeMap;
+
 use nom::{error::Error, IResult};
 
 use crate::{
-    error::{ParseErr, RomError},
+    error::{ExpansionError, ParseErr, RomError},
+    internal_header::{offsets, MapMode, RomInternalHeader},
     snes_utils::{
-        addr::{Addr, AddrPc},
+        addr::{Addr, AddrPc, AddrSnes, Sa1BankMapping},
+        mapper::Mapper,
         rom_slice::*,
     },
 };
 
 pub const SMC_HEADER_SIZE: usize = 0x200;
+pub const EXPANSION_SIZES: [usize; 4] = [0x100000, 0x200000, 0x400000, 0x800000];
+const MAX_LOROM_SIZE: usize = 0x400000;
+/// Largest ROM any of the supported mappers can address.
+pub const MAX_ROM_SIZE: usize = 0x800000;
+
+/// Copy of the original LoROM image made when expanding to ExLoROM, so that banks $00-$7D, which are
+/// mapped past 4 MB, still contain all of it. The whole upper half is reserved for the copy, since
+/// a hack bigger than the original game may use any of those banks.
+pub const EXLOROM_MIRROR: PcSlice = PcSlice::new(AddrPc(0x400000), MAX_LOROM_SIZE);
+
+pub struct Rom {
+    data:       Vec<u8>,
+    modified:   ModifiedRegions,
+    mapper:     Mapper,
+    smc_header: Option<Vec<u8>>,
+}
 
-pub struct Rom(Vec<u8>);
+/// Sorted, non-overlapping and non-adjacent ranges of modified bytes, as `begin => end` PC offsets.
+#[derive(Clone, Default)]
+struct ModifiedRegions(BTreeMap<usize, usize>);
 
 impl Rom {
     pub fn new(mut data: Vec<u8>) -> Result<Self, RomError> {
-        if !data.is_empty() {
-            let modulo_1k = data.len() % 0x400;
-            if modulo_1k == 0 {
-                Ok(Self(data))
-            } else if modulo_1k == SMC_HEADER_SIZE {
-                Ok(Self(data.drain(SMC_HEADER_SIZE..).collect()))
-            } else {
-                Err(RomError::Size(data.len()))
+        if data.is_empty() {
+            return Err(RomError::Empty);
+        }
+        let smc_header = match data.len() % 0x400 {
+            0 => None,
+            SMC_HEADER_SIZE => Some(data.drain(..SMC_HEADER_SIZE).collect()),
+            _ => return Err(RomError::Size(data.len())),
+        };
+
+        let mut rom = Self { data, modified: ModifiedRegions::default(), mapper: Mapper::default(), smc_header };
+        rom.detect_mapper();
+        Ok(rom)
+    }
+
+    /// Picks the mapper from the internal header, falling back to LoROM if it cannot be parsed.
+    pub fn detect_mapper(&mut self) {
+        self.mapper = RomInternalHeader::parse(self).map(|header| Mapper::from_header(&header)).unwrap_or_default();
+        log::info!("Using {} mapper", self.mapper);
+    }
+
+    pub fn mapper(&self) -> Mapper {
+        self.mapper
+    }
+
+    pub fn set_mapper(&mut self, mapper: Mapper) {
+        self.mapper = mapper;
+    }
+
+    /// Whether the ROM was loaded with an SMC header, which is not part of its data.
+    pub fn has_smc_header(&self) -> bool {
+        self.smc_header.is_some()
+    }
+
+    /// The SMC header the ROM was loaded with.
+    pub fn smc_header(&self) -> Option<&[u8]> {
+        self.smc_header.as_deref()
+    }
+
+    pub(crate) fn smc_header_mut(&mut self) -> Option<&mut [u8]> {
+        self.smc_header.as_deref_mut()
+    }
+
+    /// Serializes the ROM in the same form it was loaded in.
+    pub fn to_original_bytes(&self) -> Vec<u8> {
+        self.to_bytes(self.has_smc_header())
+    }
+
+    /// Serializes the ROM with or without an SMC header. The original header is used if there was one,
+    /// otherwise a blank header with only the ROM size in 8 kB units is generated.
+    pub fn to_bytes(&self, with_smc_header: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SMC_HEADER_SIZE + self.data.len());
+        if with_smc_header {
+            match self.smc_header {
+                Some(ref header) => bytes.extend_from_slice(header),
+                None => {
+                    bytes.extend_from_slice(&((self.data.len() / 0x2000) as u16).to_le_bytes());
+                    bytes.resize(SMC_HEADER_SIZE, 0);
+                }
             }
-        } else {
-            Err(RomError::Empty)
         }
+        bytes.extend_from_slice(&self.data);
+        bytes
     }
 
     pub fn slice_pc(&self, slice: PcSlice) -> Result<&[u8], RomError> {
         if slice.is_infinite() {
-            self.0.get(slice.begin.0..)
+            self.data.get(slice.begin.0..)
         } else {
-            self.0.get(slice.begin.0..slice.begin.0 + slice.size)
+            self.data.get(slice.begin.0..slice.begin.0 + slice.size)
         }
         .ok_or(RomError::SlicePc(slice))
     }
 
+    pub fn slice_snes(&self, slice: SnesSlice) -> Result<&[u8], RomError> {
+        let begin = self.mapper.to_pc(slice.begin).map_err(RomError::AddressSliceSnes)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc(pc_slice).map_err(|_| RomError::SliceSnes(slice))
+    }
+
     pub fn slice_lorom(&self, slice: SnesSlice) -> Result<&[u8], RomError> {
         let begin = AddrPc::try_from_lorom(slice.begin).map_err(RomError::AddressSliceLoRom)?;
         let pc_slice = PcSlice::new(begin, slice.size);
@@ -49,6 +127,24 @@ impl Rom {
         self.slice_pc(pc_slice).map_err(|_| RomError::SliceHiRom(slice))
     }
 
+    pub fn slice_exlorom(&self, slice: SnesSlice) -> Result<&[u8], RomError> {
+        let begin = AddrPc::try_from_exlorom(slice.begin).map_err(RomError::AddressSliceExLoRom)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc(pc_slice).map_err(|_| RomError::SliceExLoRom(slice))
+    }
+
+    pub fn slice_exhirom(&self, slice: SnesSlice) -> Result<&[u8], RomError> {
+        let begin = AddrPc::try_from_exhirom(slice.begin).map_err(RomError::AddressSliceExHiRom)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc(pc_slice).map_err(|_| RomError::SliceExHiRom(slice))
+    }
+
+    pub fn slice_sa1(&self, slice: SnesSlice, banks: Sa1BankMapping) -> Result<&[u8], RomError> {
+        let begin = AddrPc::try_from_sa1(slice.begin, banks).map_err(RomError::AddressSliceSa1)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc(pc_slice).map_err(|_| RomError::SliceSa1(slice))
+    }
+
     pub fn parse_slice_pc<'r, Parser, Ret>(&'r self, slice: PcSlice, mut parser: Parser) -> Result<Ret, RomError>
     where
         Parser: FnMut(&'r [u8]) -> IResult<&'r [u8], Ret, Error<&'r [u8]>>,
@@ -58,6 +154,15 @@ impl Rom {
         Ok(ret)
     }
 
+    pub fn parse_slice_snes<'r, Parser, Ret>(&'r self, slice: SnesSlice, parser: Parser) -> Result<Ret, RomError>
+    where
+        Parser: FnMut(&'r [u8]) -> IResult<&'r [u8], Ret, Error<&'r [u8]>>,
+    {
+        let begin = self.mapper.to_pc(slice.begin).map_err(RomError::AddressParseSnes)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.parse_slice_pc(pc_slice, parser).map_err(|_| RomError::ParseSnes(slice))
+    }
+
     pub fn parse_slice_lorom<'r, Parser, Ret>(&'r self, slice: SnesSlice, parser: Parser) -> Result<Ret, RomError>
     where
         Parser: FnMut(&'r [u8]) -> IResult<&'r [u8], Ret, Error<&'r [u8]>>,
@@ -75,4 +180,188 @@ impl Rom {
         let pc_slice = PcSlice::new(begin, slice.size);
         self.parse_slice_pc(pc_slice, parser).map_err(|_| RomError::ParseHiRom(slice))
     }
+
+    pub fn parse_slice_exlorom<'r, Parser, Ret>(&'r self, slice: SnesSlice, parser: Parser) -> Result<Ret, RomError>
+    where
+        Parser: FnMut(&'r [u8]) -> IResult<&'r [u8], Ret, Error<&'r [u8]>>,
+    {
+        let begin = AddrPc::try_from_exlorom(slice.begin).map_err(RomError::AddressParseExLoRom)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.parse_slice_pc(pc_slice, parser).map_err(|_| RomError::ParseExLoRom(slice))
+    }
+
+    pub fn parse_slice_exhirom<'r, Parser, Ret>(&'r self, slice: SnesSlice, parser: Parser) -> Result<Ret, RomError>
+    where
+        Parser: FnMut(&'r [u8]) -> IResult<&'r [u8], Ret, Error<&'r [u8]>>,
+    {
+        let begin = AddrPc::try_from_exhirom(slice.begin).map_err(RomError::AddressParseExHiRom)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.parse_slice_pc(pc_slice, parser).map_err(|_| RomError::ParseExHiRom(slice))
+    }
+
+    pub fn parse_slice_sa1<'r, Parser, Ret>(
+        &'r self, slice: SnesSlice, banks: Sa1BankMapping, parser: Parser,
+    ) -> Result<Ret, RomError>
+    where
+        Parser: FnMut(&'r [u8]) -> IResult<&'r [u8], Ret, Error<&'r [u8]>>,
+    {
+        let begin = AddrPc::try_from_sa1(slice.begin, banks).map_err(RomError::AddressParseSa1)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.parse_slice_pc(pc_slice, parser).map_err(|_| RomError::ParseSa1(slice))
+    }
+
+    pub fn slice_pc_mut(&mut self, slice: PcSlice) -> Result<&mut [u8], RomError> {
+        let begin = slice.begin.0;
+        let end = if slice.is_infinite() { self.data.len() } else { begin.saturating_add(slice.size) };
+        if begin > end || end > self.data.len() {
+            return Err(RomError::SlicePc(slice));
+        }
+        self.modified.insert(begin, end);
+        Ok(&mut self.data[begin..end])
+    }
+
+    pub fn slice_snes_mut(&mut self, slice: SnesSlice) -> Result<&mut [u8], RomError> {
+        let begin = self.mapper.to_pc(slice.begin).map_err(RomError::AddressSliceSnes)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc_mut(pc_slice).map_err(|_| RomError::SliceSnes(slice))
+    }
+
+    pub fn slice_lorom_mut(&mut self, slice: SnesSlice) -> Result<&mut [u8], RomError> {
+        let begin = AddrPc::try_from_lorom(slice.begin).map_err(RomError::AddressSliceLoRom)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc_mut(pc_slice).map_err(|_| RomError::SliceLoRom(slice))
+    }
+
+    pub fn slice_hirom_mut(&mut self, slice: SnesSlice) -> Result<&mut [u8], RomError> {
+        let begin = AddrPc::try_from_hirom(slice.begin).map_err(RomError::AddressSliceHiRom)?;
+        let pc_slice = PcSlice::new(begin, slice.size);
+        self.slice_pc_mut(pc_slice).map_err(|_| RomError::SliceHiRom(slice))
+    }
+
+    pub fn write_pc(&mut self, begin: AddrPc, bytes: &[u8]) -> Result<(), RomError> {
+        self.slice_pc_mut(PcSlice::new(begin, bytes.len()))?.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn write_snes(&mut self, begin: AddrSnes, bytes: &[u8]) -> Result<(), RomError> {
+        self.slice_snes_mut(SnesSlice::new(begin, bytes.len()))?.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn write_lorom(&mut self, begin: AddrSnes, bytes: &[u8]) -> Result<(), RomError> {
+        self.slice_lorom_mut(SnesSlice::new(begin, bytes.len()))?.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn write_hirom(&mut self, begin: AddrSnes, bytes: &[u8]) -> Result<(), RomError> {
+        self.slice_hirom_mut(SnesSlice::new(begin, bytes.len()))?.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Regions modified through mutable slices or writes since the ROM was loaded, or since
+    /// the last call to `clear_modified_regions`, sorted by address and with adjacent regions merged.
+    pub fn modified_regions(&self) -> Vec<PcSlice> {
+        self.modified.0.iter().map(|(&begin, &end)| PcSlice::new(AddrPc(begin), end - begin)).collect()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        !self.modified.0.is_empty()
+    }
+
+    pub fn clear_modified_regions(&mut self) {
+        self.modified.0.clear();
+    }
+
+    /// Grows the ROM to `size`, which must be one of `EXPANSION_SIZES`, filling new banks with zeros
+    /// so that they're seen as free space. Past 4 MB, a LoROM image is converted to ExLoROM.
+    /// The internal header is updated with the new size and checksum.
+    pub fn expand(&mut self, size: usize) -> Result<(), ExpansionError> {
+        if !EXPANSION_SIZES.contains(&size) || size <= self.data.len() {
+            return Err(ExpansionError::Size(self.data.len(), size));
+        }
+        let mut header = RomInternalHeader::parse(self).map_err(ExpansionError::Header)?;
+        let header_begin = header.location.begin;
+        let map_mode = if size > MAX_LOROM_SIZE {
+            match header.map_mode {
+                MapMode::SlowLoRom | MapMode::SlowExLoRom => MapMode::SlowExLoRom,
+                MapMode::FastLoRom | MapMode::FastExLoRom => MapMode::FastExLoRom,
+                other => return Err(ExpansionError::Unsupported(other)),
+            }
+        } else {
+            header.map_mode
+        };
+        let original_size = self.data.len();
+        let mirror_original = size > MAX_LOROM_SIZE && original_size <= MAX_LOROM_SIZE;
+        log::info!("Expanding ROM from {:#X} to {:#X} bytes ({})", self.data.len(), size, map_mode);
+
+        self.resize(size, 0x00);
+        header.map_mode = map_mode;
+        header.rom_size = (size / 0x400).trailing_zeros() as u8;
+        self.write_pc(header_begin + offsets::MAP_MODE, &[map_mode.into()]).map_err(ExpansionError::Write)?;
+        self.write_pc(header_begin + offsets::ROM_SIZE, &[header.rom_size]).map_err(ExpansionError::Write)?;
+
+        if mirror_original {
+            // Any valid checksum and complement pair sums up to the same value, so the mirrored one
+            // can be set to a placeholder before calculating the checksum and updated afterwards.
+            let mirror_cpl_csm = EXLOROM_MIRROR.begin + header_begin.0 + offsets::COMPLEMENT_CHECK;
+            let original = self.data[..original_size].to_vec();
+            self.write_pc(EXLOROM_MIRROR.begin, &original).map_err(ExpansionError::Write)?;
+            self.write_pc(mirror_cpl_csm, &[0xFF, 0xFF, 0x00, 0x00]).map_err(ExpansionError::Write)?;
+            header.update_checksum(self).map_err(ExpansionError::Write)?;
+            let cpl_csm = self.slice_pc(PcSlice::new(header_begin + offsets::COMPLEMENT_CHECK, 4));
+            let cpl_csm = cpl_csm.map_err(ExpansionError::Write)?.to_vec();
+            self.write_pc(mirror_cpl_csm, &cpl_csm).map_err(ExpansionError::Write)?;
+        } else {
+            header.update_checksum(self).map_err(ExpansionError::Write)?;
+        }
+
+        self.detect_mapper();
+        Ok(())
+    }
+
+    /// Truncates the ROM or extends it with `fill`; the extension is marked as modified.
+    pub fn resize(&mut self, new_size: usize, fill: u8) {
+        let old_size = self.data.len();
+        self.data.resize(new_size, fill);
+        if new_size > old_size {
+            self.modified.insert(old_size, new_size);
+        } else {
+            self.modified.truncate(new_size);
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl ModifiedRegions {
+    fn truncate(&mut self, size: usize) {
+        self.0.retain(|&begin, _| begin < size);
+        for end in self.0.values_mut() {
+            *end = (*end).min(size);
+        }
+    }
+
+    fn insert(&mut self, mut begin: usize, mut end: usize) {
+        if begin >= end {
+            return;
+        }
+        let overlapping: Vec<(usize, usize)> =
+            self.0.range(..=end).rev().take_while(|(_, &e)| e >= begin).map(|(&b, &e)| (b, e)).collect();
+        for (b, e) in overlapping {
+            self.0.remove(&b);
+            begin = begin.min(b);
+            end = end.max(e);
+        }
+        self.0.insert(begin, end);
+    }
 }
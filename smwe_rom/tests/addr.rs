@@ -0,0 +1,84 @@
+use smwe_rom::snes_utils::{
+    addr::{Addr, AddrPc, AddrSnes, Sa1BankMapping},
+    rom::Rom,
+    rom_slice::SnesSlice,
+};
+
+#[test]
+fn exlorom_conversions() {
+    let pairs = [(0x000000, 0x808000), (0x3FFFFF, 0xFFFFFF), (0x400000, 0x008000), (0x7EFFFF, 0x7DFFFF)];
+    for &(pc, snes) in pairs.iter() {
+        assert_eq!(AddrSnes::try_from_exlorom(AddrPc(pc)).unwrap(), AddrSnes(snes));
+        assert_eq!(AddrPc::try_from_exlorom(AddrSnes(snes)).unwrap(), AddrPc(pc));
+    }
+    assert!(AddrSnes::try_from_exlorom(AddrPc(0x7F0000)).is_err());
+    assert!(AddrPc::try_from_exlorom(AddrSnes(0x7E8000)).is_err());
+    assert!(AddrPc::try_from_exlorom(AddrSnes(0x000000)).is_err());
+    assert!(AddrPc::try_from_exlorom(AddrSnes(0x700000)).is_err());
+}
+
+#[test]
+fn exhirom_conversions() {
+    let pairs = [(0x000000, 0xC00000), (0x3FFFFF, 0xFFFFFF), (0x400000, 0x400000), (0x7DFFFF, 0x7DFFFF)];
+    for &(pc, snes) in pairs.iter() {
+        assert_eq!(AddrSnes::try_from_exhirom(AddrPc(pc)).unwrap(), AddrSnes(snes));
+        assert_eq!(AddrPc::try_from_exhirom(AddrSnes(snes)).unwrap(), AddrPc(pc));
+    }
+    assert_eq!(AddrPc::try_from_exhirom(AddrSnes(0x808000)).unwrap(), AddrPc(0x008000));
+    assert_eq!(AddrPc::try_from_exhirom(AddrSnes(0x008000)).unwrap(), AddrPc(0x408000));
+    assert!(AddrSnes::try_from_exhirom(AddrPc(0x7E0000)).is_err());
+    assert!(AddrPc::try_from_exhirom(AddrSnes(0x7F0000)).is_err());
+    assert!(AddrPc::try_from_exhirom(AddrSnes(0x800000)).is_err());
+}
+
+#[test]
+fn sa1_default_conversions() {
+    let banks = Sa1BankMapping::default();
+    let pairs =
+        [(0x000000, 0x008000), (0x0FFFFF, 0x1FFFFF), (0x100000, 0x208000), (0x200000, 0x808000), (0x3FFFFF, 0xBFFFFF)];
+    for &(pc, snes) in pairs.iter() {
+        assert_eq!(AddrSnes::try_from_sa1(AddrPc(pc), banks).unwrap(), AddrSnes(snes));
+        assert_eq!(AddrPc::try_from_sa1(AddrSnes(snes), banks).unwrap(), AddrPc(pc));
+    }
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0xC12345), banks).unwrap(), AddrPc(0x012345));
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0xF00000), banks).unwrap(), AddrPc(0x300000));
+    assert!(AddrSnes::try_from_sa1(AddrPc(0x400000), banks).is_err());
+    assert!(AddrPc::try_from_sa1(AddrSnes(0x400000), banks).is_err());
+    assert!(AddrPc::try_from_sa1(AddrSnes(0x000000), banks).is_err());
+}
+
+#[test]
+fn sa1_custom_conversions() {
+    let banks = Sa1BankMapping([0x80, 0x81, 0x87, 0x84]);
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0x808000), banks).unwrap(), AddrPc(0x700000));
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0xE00010), banks).unwrap(), AddrPc(0x700010));
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0xA18000), banks).unwrap(), AddrPc(0x408000));
+    assert_eq!(AddrSnes::try_from_sa1(AddrPc(0x408000), banks).unwrap(), AddrSnes(0xA18000));
+    assert!(AddrSnes::try_from_sa1(AddrPc(0x200000), banks).is_err());
+}
+
+#[test]
+fn sa1_lorom_area_without_bit_7() {
+    let banks = Sa1BankMapping([0, 1, 7, 4]);
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0x808000), banks).unwrap(), AddrPc(0x200000));
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0xA18000), banks).unwrap(), AddrPc(0x308000));
+    assert_eq!(AddrPc::try_from_sa1(AddrSnes(0xE00010), banks).unwrap(), AddrPc(0x700010));
+    assert_eq!(AddrSnes::try_from_sa1(AddrPc(0x200000), banks).unwrap(), AddrSnes(0x808000));
+    assert_eq!(AddrSnes::try_from_sa1(AddrPc(0x700010), banks).unwrap(), AddrSnes(0xE00010));
+    assert!(AddrSnes::try_from_sa1(AddrPc(0x500000), banks).is_err());
+}
+
+#[test]
+fn extended_slicing() {
+    let mut data = vec![0; 0x600000];
+    data[0x000010] = 1;
+    data[0x400010] = 2;
+    let rom = Rom::new(data).unwrap();
+
+    assert_eq!(rom.slice_exlorom(SnesSlice::new(AddrSnes(0x808010), 1)).unwrap(), &[1]);
+    assert_eq!(rom.slice_exlorom(SnesSlice::new(AddrSnes(0x008010), 1)).unwrap(), &[2]);
+    assert_eq!(rom.slice_exhirom(SnesSlice::new(AddrSnes(0xC00010), 1)).unwrap(), &[1]);
+    assert_eq!(rom.slice_exhirom(SnesSlice::new(AddrSnes(0x400010), 1)).unwrap(), &[2]);
+    assert_eq!(rom.slice_sa1(SnesSlice::new(AddrSnes(0xE00010), 1), Sa1BankMapping([0, 1, 4, 3])).unwrap(), &[2]);
+    assert!(rom.slice_exhirom(SnesSlice::new(AddrSnes(0x600000), 1)).is_err());
+}
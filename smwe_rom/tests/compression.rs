@@ -0,0 +1,267 @@
+#[macro_use]
+extern crate quickcheck;
+
+use smwe_rom::compression::{lc_lz2, lc_lz3, lc_rle1};
+
+fn lz2_round_trip(data: &[u8]) -> Vec<u8> {
+    let compressed = lc_lz2::compress(data);
+    let decompressed = lc_lz2::decompress(&compressed).expect("Failed to decompress LC_LZ2 data");
+    assert_eq!(data, decompressed.output.as_slice());
+    assert_eq!(compressed.len(), decompressed.input_len);
+    compressed
+}
+
+fn lz3_round_trip(data: &[u8]) -> Vec<u8> {
+    let compressed = lc_lz3::compress(data);
+    let decompressed = lc_lz3::decompress(&compressed).expect("Failed to decompress LC_LZ3 data");
+    assert_eq!(data, decompressed.output.as_slice());
+    assert_eq!(compressed.len(), decompressed.input_len);
+    compressed
+}
+
+fn rle1_round_trip(data: &[u8]) -> Vec<u8> {
+    let compressed = lc_rle1::compress(data);
+    let decompressed = lc_rle1::decompress(&compressed).expect("Failed to decompress LC_RLE1 data");
+    assert_eq!(data, decompressed.output.as_slice());
+    assert_eq!(compressed.len(), decompressed.input_len);
+    compressed
+}
+
+fn pseudo_random_bytes(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn lz2_empty() {
+    assert_eq!(lz2_round_trip(&[]), vec![0xFF]);
+}
+
+#[test]
+fn lz2_fills() {
+    assert_eq!(lz2_round_trip(&[0x42; 20]), vec![0x33, 0x42, 0xFF]);
+    assert_eq!(lz2_round_trip(&[0xAB, 0xCD].repeat(8)), vec![0x4F, 0xAB, 0xCD, 0xFF]);
+    assert_eq!(lz2_round_trip(&(0xF8..=0xFF).chain(0x00..0x08).collect::<Vec<u8>>()), vec![0x6F, 0xF8, 0xFF]);
+}
+
+#[test]
+fn lz2_long_lengths() {
+    assert_eq!(lz2_round_trip(&[0x00; 1024]), vec![0xE7, 0xFF, 0x00, 0xFF]);
+    lz2_round_trip(&[0x00; 5000]);
+    lz2_round_trip(&pseudo_random_bytes(3000, 7));
+}
+
+#[test]
+fn lz2_repeats() {
+    let pattern = pseudo_random_bytes(100, 1);
+    let data = [pattern.as_slice(), &[0x11; 40], pattern.as_slice(), pattern.as_slice()].concat();
+    let compressed = lz2_round_trip(&data);
+    assert!(compressed.len() < pattern.len() + 20);
+}
+
+#[test]
+fn lz2_mixed_data() {
+    for seed in 0..50 {
+        let mut data = Vec::new();
+        let chunks = pseudo_random_bytes(64, seed);
+        for pair in chunks.chunks(2) {
+            let len = pair[1] as usize % 40 + 1;
+            match pair[0] % 5 {
+                0 => data.extend(pseudo_random_bytes(len, seed + len as u32)),
+                1 => data.extend(vec![pair[1]; len]),
+                2 => data.extend([pair[0], pair[1]].iter().cycle().take(len)),
+                3 => data.extend((0..len).map(|k| pair[1].wrapping_add(k as u8))),
+                _ if data.len() > len => {
+                    let repeated = data[data.len() / 3..data.len() / 3 + len.min(data.len() / 2)].to_vec();
+                    data.extend(repeated)
+                }
+                _ => data.push(pair[1]),
+            }
+        }
+        lz2_round_trip(&data);
+    }
+}
+
+#[test]
+fn lz3_commands() {
+    assert_eq!(lz3_round_trip(&[]), vec![0xFF]);
+    assert_eq!(lz3_round_trip(&[0x00; 16]), vec![0x6F, 0xFF]);
+    assert_eq!(lc_lz3::decompress(&[0x03, 1, 2, 3, 4, 0x83, 0x80, 0xFF]).unwrap().output, [1, 2, 3, 4, 4, 4, 4, 4]);
+    assert_eq!(lc_lz3::decompress(&[0x01, 0x01, 0x80, 0xA1, 0x00, 0x00, 0xFF]).unwrap().output, [
+        0x01, 0x80, 0x80, 0x01
+    ]);
+    assert_eq!(lc_lz3::decompress(&[0x02, 1, 2, 3, 0xC2, 0x80, 0xFF]).unwrap().output, [1, 2, 3, 3, 2, 1]);
+    assert_eq!(lc_lz3::decompress(&[0x02, 1, 2, 3, 0xC1, 0x00, 0x01, 0xFF]).unwrap().output, [1, 2, 3, 2, 1]);
+
+    let pattern = pseudo_random_bytes(64, 9);
+    let reversed_bits: Vec<u8> = pattern.iter().map(|b| b.reverse_bits()).collect();
+    let backwards: Vec<u8> = pattern.iter().rev().copied().collect();
+    let data = [pattern.as_slice(), &reversed_bits, &backwards, &[0; 100], &pattern].concat();
+    assert!(lz3_round_trip(&data).len() < pattern.len() + 20);
+    lz3_round_trip(&pseudo_random_bytes(40000, 5));
+}
+
+#[test]
+fn lz3_malformed_input() {
+    let err = lc_lz3::decompress(&[0x21, 0x10, 0x82, 0x82, 0xFF]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (4, Some("Repeat"), 2));
+
+    let err = lc_lz3::decompress(&[0x21, 0x10, 0xC3, 0x81, 0xFF]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (4, Some("BackwardsRepeat"), 2));
+
+    let err = lc_lz3::decompress(&[0x21, 0x10, 0xA0, 0x00, 0x02, 0xFF]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (5, Some("BitReversedRepeat"), 2));
+}
+
+#[test]
+fn rle1_runs() {
+    assert_eq!(rle1_round_trip(&[]), vec![0xFF, 0xFF]);
+    assert_eq!(rle1_round_trip(&[0x25; 128]), vec![0xFF, 0x25, 0xFF, 0xFF]);
+    assert_eq!(rle1_round_trip(&[0xFF; 128]).len(), 6);
+    assert_eq!(rle1_round_trip(&[0x01, 0x02, 0x03]), vec![0x02, 0x01, 0x02, 0x03, 0xFF, 0xFF]);
+    rle1_round_trip(&[0xFF; 1000]);
+}
+
+quickcheck! {
+    fn lz2_round_trips_arbitrary_bytes(data: Vec<u8>) -> bool {
+        matches!(lc_lz2::decompress(&lc_lz2::compress(&data)), Ok(d) if d.output == data)
+    }
+
+    fn lz3_round_trips_arbitrary_bytes(data: Vec<u8>) -> bool {
+        matches!(lc_lz3::decompress(&lc_lz3::compress(&data)), Ok(d) if d.output == data)
+    }
+
+    fn rle1_round_trips_arbitrary_bytes(data: Vec<u8>) -> bool {
+        matches!(lc_rle1::decompress(&lc_rle1::compress(&data)), Ok(d) if d.output == data)
+    }
+
+    fn rle1_round_trips_runs(runs: Vec<(u8, u8)>) -> bool {
+        let data: Vec<u8> = runs.into_iter().flat_map(|(byte, len)| vec![byte; len as usize]).collect();
+        matches!(lc_rle1::decompress(&lc_rle1::compress(&data)), Ok(d) if d.output == data)
+    }
+}
+
+#[test]
+fn lz2_malformed_input() {
+    let err = lc_lz2::decompress(&[]).unwrap_err();
+    assert_eq!((err.offset, err.command, err.output_len), (0, None, 0));
+
+    let err = lc_lz2::decompress(&[0x21, 0x55, 0x04, 0x01, 0x02]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (3, Some("DirectCopy"), 2));
+
+    let err = lc_lz2::decompress(&[0x21, 0x55, 0x83, 0x00, 0x02, 0xFF]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (5, Some("Repeat"), 2));
+
+    let err = lc_lz2::decompress(&[0xE0]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (1, Some("DirectCopy"), 0));
+
+    let err = lc_lz2::decompress(&[0xA0, 0x00]).unwrap_err();
+    assert_eq!((err.offset, err.command, err.output_len), (1, None, 0));
+
+    let err = lc_lz2::decompress(&[0xFC, 0x00]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (2, Some("LongLength"), 0));
+}
+
+#[test]
+fn lz2_overlapping_repeat() {
+    assert_eq!(lc_lz2::decompress(&[0x01, 0x01, 0x02, 0x85, 0x00, 0x00, 0xFF]).unwrap().output, [
+        1, 2, 1, 2, 1, 2, 1, 2
+    ]);
+}
+
+#[test]
+fn rle1_malformed_input() {
+    let err = lc_rle1::decompress(&[]).unwrap_err();
+    assert_eq!((err.offset, err.command, err.output_len), (0, None, 0));
+
+    let err = lc_rle1::decompress(&[0x83, 0x10, 0x05, 0x01]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (3, Some("DirectCopy"), 4));
+
+    let err = lc_rle1::decompress(&[0x83]).unwrap_err();
+    assert_eq!((err.offset, err.command.as_deref(), err.output_len), (1, Some("ByteFill"), 0));
+
+    assert_eq!(lc_rle1::decompress(&[0x80, 0x10, 0xFF]).unwrap().output, [0x10]);
+}
+
+#[test]
+fn consumed_input_length() {
+    let trailing = [0x12, 0x34, 0xFF, 0xFF];
+    let data = pseudo_random_bytes(200, 11);
+    for (compressed, decompressed) in [
+        (lc_lz2::compress(&data), lc_lz2::decompress(&[lc_lz2::compress(&data), trailing.to_vec()].concat())),
+        (lc_lz3::compress(&data), lc_lz3::decompress(&[lc_lz3::compress(&data), trailing.to_vec()].concat())),
+        (lc_rle1::compress(&data), lc_rle1::decompress(&[lc_rle1::compress(&data), trailing.to_vec()].concat())),
+    ]
+    .iter()
+    {
+        let decompressed = decompressed.as_ref().unwrap();
+        assert_eq!(decompressed.output, data);
+        assert_eq!(decompressed.input_len, compressed.len());
+    }
+    assert_eq!(lc_rle1::decompress(&[0x80, 0x10, 0xFF]).unwrap().input_len, 3);
+}
+
+#[test]
+fn truncated_streams_are_rejected() {
+    let data = pseudo_random_bytes(600, 3).into_iter().chain(vec![0x44; 100]).collect::<Vec<u8>>();
+    let lz2 = lc_lz2::compress(&data);
+    for end in 0..lz2.len() - 1 {
+        assert!(lc_lz2::decompress(&lz2[..end]).is_err(), "Accepted LC_LZ2 data truncated at {}", end);
+    }
+    let rle1 = lc_rle1::compress(&data);
+    for end in 0..rle1.len() - 2 {
+        assert!(lc_rle1::decompress(&rle1[..end]).is_err(), "Accepted LC_RLE1 data truncated at {}", end);
+    }
+}
+
+quickcheck! {
+    fn lz2_decompress_never_panics(data: Vec<u8>) -> bool {
+        let _ = lc_lz2::decompress(&data);
+        true
+    }
+
+    fn lz3_decompress_never_panics(data: Vec<u8>) -> bool {
+        let _ = lc_lz3::decompress(&data);
+        true
+    }
+
+    fn rle1_decompress_never_panics(data: Vec<u8>) -> bool {
+        let _ = lc_rle1::decompress(&data);
+        true
+    }
+
+    fn lz2_decompress_corrupted_never_panics(data: Vec<u8>, corruptions: Vec<(usize, u8)>) -> bool {
+        let mut compressed = lc_lz2::compress(&data);
+        for (idx, byte) in corruptions {
+            let len = compressed.len();
+            compressed[idx % len] = byte;
+        }
+        let _ = lc_lz2::decompress(&compressed);
+        true
+    }
+
+    fn lz3_decompress_corrupted_never_panics(data: Vec<u8>, corruptions: Vec<(usize, u8)>) -> bool {
+        let mut compressed = lc_lz3::compress(&data);
+        for (idx, byte) in corruptions {
+            let len = compressed.len();
+            compressed[idx % len] = byte;
+        }
+        let _ = lc_lz3::decompress(&compressed);
+        true
+    }
+
+    fn rle1_decompress_corrupted_never_panics(data: Vec<u8>, corruptions: Vec<(usize, u8)>) -> bool {
+        let mut compressed = lc_rle1::compress(&data);
+        for (idx, byte) in corruptions {
+            let len = compressed.len();
+            compressed[idx % len] = byte;
+        }
+        let _ = lc_rle1::decompress(&compressed);
+        true
+    }
+}
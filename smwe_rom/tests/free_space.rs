@@ -0,0 +1,81 @@
+use smwe_rom::{
+    error::FreeSpaceError,
+    snes_utils::{
+        addr::AddrPc,
+        free_space::{FreeSpace, RatsTag, RATS_TAG_SIZE},
+        rom::Rom,
+        rom_slice::PcSlice,
+    },
+};
+
+fn expanded_rom() -> Rom {
+    let mut data = vec![0xAA; 0x80000];
+    data.resize(0x100000, 0x00);
+    Rom::new(data).expect("Invalid ROM size")
+}
+
+#[test]
+fn rats_tags_are_respected() {
+    let mut rom = expanded_rom();
+    rom.write_pc(AddrPc(0x80000), &RatsTag::to_bytes(0x10)).unwrap();
+    rom.write_pc(AddrPc(0x90000), &RatsTag::to_bytes(0x8000 - RATS_TAG_SIZE)).unwrap();
+
+    let free_space = FreeSpace::scan(&rom);
+    let blocks: Vec<(usize, usize)> = free_space.free_blocks().iter().map(|s| (s.begin.0, s.size)).collect();
+    assert_eq!(blocks, [(0x80018, 0x10000 - 0x18), (0x98000, 0x68000)]);
+    assert_eq!(free_space.total_free(), 0x80000 - 0x8018);
+}
+
+#[test]
+fn allocate_and_free() {
+    let mut rom = expanded_rom();
+    let mut free_space = FreeSpace::scan(&rom);
+    let total = free_space.total_free();
+
+    let first = free_space.allocate(&mut rom, &[1; 0x100]).unwrap();
+    assert_eq!(first, AddrPc(0x80000 + RATS_TAG_SIZE));
+    let tag = RatsTag::read(&rom, AddrPc(0x80000)).unwrap();
+    assert_eq!(tag.data_size, 0x100);
+    assert_eq!(tag.data_begin(), first);
+
+    let second = free_space.allocate(&mut rom, &[2; 0x8000 - RATS_TAG_SIZE]).unwrap();
+    assert_eq!(second, AddrPc(0x88000 + RATS_TAG_SIZE), "Allocation must not cross a bank boundary");
+    assert_eq!(FreeSpace::scan(&rom).total_free(), free_space.total_free());
+
+    free_space.free(&mut rom, first).unwrap();
+    free_space.free(&mut rom, second).unwrap();
+    assert_eq!(free_space.total_free(), total);
+    assert!(rom.as_bytes()[0x80000..].iter().all(|&b| b == 0));
+    assert!(matches!(free_space.free(&mut rom, first), Err(FreeSpaceError::NoTag(_))));
+}
+
+#[test]
+fn allocation_errors() {
+    let mut rom = expanded_rom();
+    let mut free_space = FreeSpace::scan(&rom);
+    assert!(matches!(free_space.allocate(&mut rom, &[]), Err(FreeSpaceError::InvalidSize(0))));
+    assert!(matches!(free_space.allocate(&mut rom, &[1; 0x8000]), Err(FreeSpaceError::NoSpace(0x8000))));
+
+    free_space.protect(PcSlice::new(AddrPc(0x80000), 0x80000));
+    assert!(matches!(free_space.allocate(&mut rom, &[1]), Err(FreeSpaceError::NoSpace(1))));
+}
+
+#[test]
+fn fragmentation_and_bank_usage() {
+    let mut rom = expanded_rom();
+    let mut free_space = FreeSpace::scan(&rom);
+    assert_eq!(free_space.fragmentation(), 0.0);
+
+    free_space.allocate(&mut rom, &[1; 0x3FF8]).unwrap();
+    let usage = free_space.bank_usage();
+    assert_eq!(usage.len(), 32);
+    assert_eq!((usage[0].used(), usage[15].used()), (0x8000, 0x8000));
+    assert_eq!((usage[16].free, usage[16].used()), (0x4000, 0x4000));
+    assert_eq!(usage[17].free, 0x8000);
+    assert_eq!(free_space.largest_free_block(), 0x8000);
+
+    free_space.protect(PcSlice::new(AddrPc(0x88000), 0x78000));
+    free_space.protect(PcSlice::new(AddrPc(0x84800), 0x800));
+    assert_eq!(free_space.total_free(), 0x3800);
+    assert_eq!(free_space.fragmentation(), 1.0 - 0x3000 as f64 / 0x3800 as f64);
+}
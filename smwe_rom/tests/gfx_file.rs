@@ -0,0 +1,110 @@
+use smwe_rom::{
+    compression::Codec,
+    error::GfxFileParseError,
+    graphics::{
+        color::Abgr1555,
+        gfx_file::{GfxFile, Tile, TileFormat, GFX_FILES_META, GFX_POINTER_TABLES},
+    },
+    snes_utils::{addr::AddrSnes, rom::Rom},
+};
+
+fn rom_with_gfx_file_0(data: &[u8]) -> Rom {
+    let mut rom = Rom::new(vec![0; 0x80000]).expect("Invalid ROM size");
+    set_gfx_pointer(&mut rom, 0, GFX_FILES_META[0].1.begin);
+    rom.write_snes(GFX_FILES_META[0].1.begin, data).unwrap();
+    rom
+}
+
+fn set_gfx_pointer(rom: &mut Rom, file_num: usize, addr: AddrSnes) {
+    for (table, byte) in GFX_POINTER_TABLES.iter().zip(addr.0.to_le_bytes().iter()) {
+        rom.write_snes(*table + file_num, &[*byte]).unwrap();
+    }
+}
+
+#[test]
+fn decode_3bpp_planes() {
+    let mut bytes = vec![0u8; 24];
+    bytes[0x00] = 0b1000_0001; // Row 0, plane 0
+    bytes[0x01] = 0b0100_0001; // Row 0, plane 1
+    bytes[0x10] = 0b0010_0001; // Row 0, plane 2
+    bytes[0x0F] = 0b0000_0001; // Row 7, plane 1
+    bytes[0x17] = 0b1000_0000; // Row 7, plane 2
+    bytes.push(0xAA);
+
+    let (rest, tile) = Tile::from_3bpp(&bytes).unwrap();
+    assert_eq!(rest, &[0xAA]);
+    let indices = tile.color_indices();
+    assert_eq!(&indices[0..8], &[1, 2, 4, 0, 0, 0, 0, 7]);
+    assert_eq!(&indices[56..64], &[4, 0, 0, 0, 0, 0, 0, 2]);
+    assert!(indices[8..56].iter().all(|&i| i == 0));
+}
+
+#[test]
+fn export_indexed_png() {
+    // Tile n has its first pixel set to colour n % 4.
+    let tiles = (0..17)
+        .map(|n| {
+            let bytes = [(n & 1) << 7, (n & 2) << 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+            Tile::from_2bpp(&bytes).unwrap().1
+        })
+        .collect();
+    let gfx_file = GfxFile { tile_format: TileFormat::Tile2bpp, compression: Codec::LcLz2, tiles };
+    let palette = [Abgr1555::TRANSPARENT, Abgr1555::WHITE, Abgr1555::RED, Abgr1555::BLUE];
+
+    let mut png_bytes = Vec::new();
+    gfx_file.write_indexed_png(&mut png_bytes, &palette).unwrap();
+
+    let mut decoder = png::Decoder::new(png_bytes.as_slice());
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height, info.color_type), (128, 16, png::ColorType::Indexed));
+    assert_eq!(reader.info().palette.as_deref(), Some(&[0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0, 255][..]));
+    assert_eq!(reader.info().trns.as_deref(), Some(&[0, 255, 255, 255][..]));
+
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+    for (n, tile) in gfx_file.tiles.iter().enumerate() {
+        let (x, y) = (8 * (n % 16), 8 * (n / 16));
+        for (i, &color_index) in tile.color_indices().iter().enumerate() {
+            assert_eq!(pixels[(y + i / 8) * 128 + x + i % 8], color_index);
+        }
+        assert_eq!(tile.color_indices()[0], n as u8 % 4);
+    }
+    // The rest of the last row of tiles is left empty.
+    assert!(pixels.chunks_exact(128).skip(8).all(|row| row[8..].iter().all(|&i| i == 0)));
+}
+
+#[test]
+fn gfx_file_codec_detection() {
+    // A partial tile is an error rather than a panic.
+    let rom = rom_with_gfx_file_0(&[0x00, 0xAA, 0xFF]);
+    assert!(matches!(GfxFile::new(&rom, 0), Err(GfxFileParseError::DecompressedSize(1, 24))));
+
+    // One tile copied directly and then bit-reversed, which only LC_LZ3 can do.
+    let mut data = vec![0x17];
+    data.extend(0..24);
+    data.extend_from_slice(&[0xB7, 0x97, 0xFF]);
+    let rom = rom_with_gfx_file_0(&data);
+    let gfx_file = GfxFile::new(&rom, 0).unwrap();
+    assert_eq!((gfx_file.compression, gfx_file.tiles.len()), (Codec::LcLz3, 2));
+    assert!(GfxFile::with_codec(&rom, 0, Codec::LcLz2).is_err());
+    assert_eq!(GfxFile::with_codec(&rom, 0, Codec::LcLz3).unwrap().tiles.len(), 2);
+}
+
+#[test]
+fn gfx_file_is_read_from_pointer_tables() {
+    // A file moved elsewhere and much bigger than the original one, as in hacks with custom graphics.
+    let mut data = Vec::new();
+    for _ in 0..200 {
+        data.push(0x17);
+        data.extend(0..24);
+    }
+    data.push(0xFF);
+    let mut rom = rom_with_gfx_file_0(&[0x00, 0xAA, 0xFF]);
+    set_gfx_pointer(&mut rom, 0, AddrSnes(0x0F8000));
+    rom.write_snes(AddrSnes(0x0F8000), &data).unwrap();
+
+    assert_eq!(GfxFile::address(&rom, 0).unwrap(), AddrSnes(0x0F8000));
+    assert_eq!(GfxFile::new(&rom, 0).unwrap().tiles.len(), 200);
+    assert_eq!(GfxFile::address(&rom, 0x33).unwrap(), GFX_FILES_META[0x33].1.begin);
+}
@@ -0,0 +1,60 @@
+use smwe_rom::{
+    compression::Codec,
+    error::GfxSetError,
+    graphics::{
+        gfx_file::{GfxFile, Tile, TileFormat},
+        gfx_set::{GfxSets, GfxSlot, SPRITE_SET_COUNT, TILESET_COUNT},
+    },
+    level::PrimaryHeaderBuilder,
+    snes_utils::{addr::AddrSnes, rom::Rom},
+};
+
+fn rom_with_gfx_lists() -> Rom {
+    let mut rom = Rom::new(vec![0; 0x80000]).expect("Invalid ROM size");
+    rom.write_snes(AddrSnes(0x00A92B + 4 * 0x0E), &[0x1C, 0x1D, 0x08, 0x1E]).unwrap();
+    rom.write_snes(AddrSnes(0x00A8C3 + 4 * 0x03), &[0x00, 0x01, 0x13, 0x05]).unwrap();
+    rom
+}
+
+#[test]
+fn level_slots_follow_header() {
+    let gfx_sets = GfxSets::parse(&rom_with_gfx_lists()).unwrap();
+    assert_eq!((gfx_sets.tilesets.len(), gfx_sets.sprite_sets.len()), (TILESET_COUNT, SPRITE_SET_COUNT));
+
+    let header = PrimaryHeaderBuilder { fg_bg_gfx: 0x0E, sprite_gfx: 0x03, ..Default::default() }.build().unwrap();
+    assert_eq!(gfx_sets.level_file_nums(&header).unwrap(), [
+        (GfxSlot::Sp1, 0x00),
+        (GfxSlot::Sp2, 0x01),
+        (GfxSlot::Sp3, 0x13),
+        (GfxSlot::Sp4, 0x05),
+        (GfxSlot::Fg1, 0x1C),
+        (GfxSlot::Fg2, 0x1D),
+        (GfxSlot::Bg1, 0x08),
+        (GfxSlot::Fg3, 0x1E),
+    ]);
+
+    let header = PrimaryHeaderBuilder { fg_bg_gfx: 0x0F, ..Default::default() }.build().unwrap();
+    assert!(matches!(gfx_sets.level_file_nums(&header), Err(GfxSetError::Tileset(0x0F))));
+}
+
+#[test]
+fn level_slots_resolve_files() {
+    let gfx_sets = GfxSets::parse(&rom_with_gfx_lists()).unwrap();
+    // Every file has as many tiles as its number, to tell them apart.
+    let gfx_files: Vec<GfxFile> = (0..=0x1E)
+        .map(|file_num| GfxFile {
+            tile_format: TileFormat::Tile3bpp,
+            compression: Codec::LcLz2,
+            tiles:       vec![Tile::from_3bpp(&[0; 24]).unwrap().1; file_num],
+        })
+        .collect();
+
+    let header = PrimaryHeaderBuilder { fg_bg_gfx: 0x0E, sprite_gfx: 0x03, ..Default::default() }.build().unwrap();
+    let slots = gfx_sets.level_slots(&header, &gfx_files).unwrap();
+    let tile_counts: Vec<_> = slots.iter().map(|(slot, file)| (*slot, file.tiles.len())).collect();
+    assert_eq!(tile_counts[2], (GfxSlot::Sp3, 0x13));
+    assert_eq!(tile_counts[6], (GfxSlot::Bg1, 0x08));
+
+    // Without file 0x1E, FG3 is missing.
+    assert!(matches!(gfx_sets.level_slots(&header, &gfx_files[..0x1E]), Err(GfxSetError::File(0x1E))));
+}
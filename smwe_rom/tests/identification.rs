@@ -0,0 +1,37 @@
+use smwe_rom::{
+    identification::{RomIdentity, RomKind},
+    snes_utils::rom::Rom,
+};
+
+fn rom_with_name(name: &[u8; 21], smc_header: bool) -> Vec<u8> {
+    let mut data = vec![0; 0x80000];
+    let header = &mut data[0x7FC0..0x7FE0];
+    header[..21].copy_from_slice(name);
+    header[21..28].copy_from_slice(&[0x20, 0x02, 0x09, 0x01, 0x01, 0x01, 0x00]);
+    header[28..32].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    if smc_header {
+        data.splice(0..0, vec![0; 0x200]);
+    }
+    data
+}
+
+#[test]
+fn identify_unrecognised_and_unknown() {
+    let rom = Rom::new(rom_with_name(b"SUPER MARIOWORLD     ", true)).unwrap();
+    let identity = RomIdentity::identify(&rom);
+    assert_eq!(identity.kind, RomKind::Unrecognised);
+    assert!(identity.has_smc_header);
+    assert!(!identity.is_clean());
+
+    let rom = Rom::new(rom_with_name(b"SOME OTHER GAME      ", false)).unwrap();
+    let identity = RomIdentity::identify(&rom);
+    assert_eq!(identity.kind, RomKind::Unknown);
+    assert!(!identity.has_smc_header);
+}
+
+#[test]
+fn headered_and_unheadered_hash_the_same() {
+    let headered = RomIdentity::identify(&Rom::new(rom_with_name(b"SUPER MARIOWORLD     ", true)).unwrap());
+    let unheadered = RomIdentity::identify(&Rom::new(rom_with_name(b"SUPER MARIOWORLD     ", false)).unwrap());
+    assert_eq!(headered.crc32, unheadered.crc32);
+}
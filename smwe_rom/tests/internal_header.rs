@@ -0,0 +1,50 @@
+use smwe_rom::{
+    snes_utils::{addr::AddrPc, rom::Rom},
+    RomInternalHeader,
+};
+
+fn rom_with_header(size: usize) -> Rom {
+    let mut data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    let header = &mut data[0x7FC0..0x7FE0];
+    header[..21].copy_from_slice(b"SUPER MARIOWORLD     ");
+    header[21..28].copy_from_slice(&[0x20, 0x02, 0x09, 0x01, 0x01, 0x01, 0x00]);
+    header[28..32].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    Rom::new(data).expect("Invalid ROM size")
+}
+
+fn sum(data: &[u8]) -> u32 {
+    data.iter().map(|&b| b as u32).sum()
+}
+
+#[test]
+fn checksum_of_power_of_two_rom() {
+    let mut rom = rom_with_header(0x80000);
+    let expected = sum(rom.as_bytes()) as u16;
+
+    let mut header = RomInternalHeader::parse(&rom).expect("Could not parse internal header");
+    assert_eq!(header.actual_checksum, expected);
+    assert!(!header.checksum_valid());
+
+    header.update_checksum(&mut rom).unwrap();
+    let cpl_csm = rom.slice_pc(header.location.offset_forward(0x1C).resize(4)).unwrap();
+    assert_eq!(cpl_csm, [!expected as u8, (!expected >> 8) as u8, expected as u8, (expected >> 8) as u8]);
+
+    let header = RomInternalHeader::parse(&rom).expect("Could not parse internal header");
+    assert_eq!((header.checksum, header.complement_check), (expected, !expected));
+    assert!(header.checksum_valid());
+}
+
+#[test]
+fn checksum_of_mirrored_rom() {
+    let mut rom = rom_with_header(0x60000);
+    let data = rom.as_bytes();
+    let expected = (sum(&data[..0x40000]) + 2 * sum(&data[0x40000..])) as u16;
+
+    let mut header = RomInternalHeader::parse(&rom).expect("Could not parse internal header");
+    assert_eq!(header.actual_checksum, expected);
+
+    header.update_checksum(&mut rom).unwrap();
+    rom.write_pc(AddrPc(0x50000), &[0x42]).unwrap();
+    let header = RomInternalHeader::parse(&rom).expect("Could not parse internal header");
+    assert!(!header.checksum_valid());
+}
@@ -0,0 +1,262 @@
+use smwe_rom::{
+    compression::lc_rle1,
+    error::{LevelHeaderError, LevelWriteError},
+    level::{
+        BackgroundData,
+        ItemMemory,
+        Layer2Data,
+        Level,
+        LevelMode,
+        Music,
+        ObjectLayer,
+        PrimaryHeader,
+        PrimaryHeaderBuilder,
+        SecondaryHeaderBuilder,
+        SpriteHeaderBuilder,
+        SpriteLayer,
+        Timer,
+        VerticalScroll,
+    },
+    snes_utils::{
+        addr::{AddrPc, AddrSnes},
+        free_space::{FreeSpace, RatsTag, RATS_TAG_SIZE},
+        rom::Rom,
+        rom_slice::SnesSlice,
+    },
+};
+
+const LEVEL: usize = 0x105;
+
+const LAYER1: &[u8] = &[0x21, 0x42, 0x13, 0x0A, 0x81, 0x81, 0x23, 0x45, 0x02, 0x00, 0x00, 0x10, 0x03, 0x00, 0x01, 0xFF];
+const SPRITES: &[u8] = &[0x80, 0x10, 0x20, 0x31, 0xFF];
+const BACKGROUND: &[u8] = &[1, 2, 3, 3, 3, 3, 3, 3];
+
+/// A ROM with a single level: Layer1 at $068000, a background at $0C8005 and sprites at $078100.
+/// Banks $07 and $0C have some free space at their end, as does everything past 512 KiB.
+fn rom_with_level() -> Rom {
+    let mut data = vec![0xAA; 0x80000];
+    data[0x3C000..0x40000].fill(0);
+    data[0x64000..0x68000].fill(0);
+    data.resize(0x100000, 0x00);
+    let mut rom = Rom::new(data).expect("Invalid ROM size");
+
+    rom.write_snes(AddrSnes(0x05E000 + 3 * LEVEL), &[0x00, 0x80, 0x06]).unwrap();
+    rom.write_snes(AddrSnes(0x05E600 + 3 * LEVEL), &[0x00, 0x80, 0xFF]).unwrap();
+    rom.write_snes(AddrSnes(0x05EC00 + 2 * LEVEL), &[0x00, 0x81]).unwrap();
+    for (i, table) in [0x05F000, 0x05F200, 0x05F400, 0x05F600].iter().enumerate() {
+        rom.write_snes(AddrSnes(table + LEVEL), &[0x11 * i as u8]).unwrap();
+    }
+    rom.write_snes(AddrSnes(0x068000), LAYER1).unwrap();
+    rom.write_snes(AddrSnes(0x0C8005), &lc_rle1::compress(BACKGROUND)).unwrap();
+    rom.write_snes(AddrSnes(0x078100), SPRITES).unwrap();
+    rom
+}
+
+fn read_pointer(rom: &Rom, table: usize, size: usize) -> usize {
+    let bytes = rom.slice_snes(SnesSlice::new(AddrSnes(table), size)).unwrap();
+    bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as usize)
+}
+
+fn layer1_pointer(rom: &Rom) -> usize {
+    read_pointer(rom, 0x05E000 + 3 * LEVEL, 3)
+}
+
+#[test]
+fn unchanged_level_stays_in_place() {
+    let mut rom = rom_with_level();
+    let original = rom.as_bytes().to_vec();
+    let mut free_space = FreeSpace::scan_from(&rom, AddrPc(0), 0x00);
+
+    let level = Level::parse(&rom, LEVEL).unwrap();
+    assert_eq!(level.layer1.to_bytes(), &LAYER1[5..]);
+    assert_eq!(level.sprite_layer.to_bytes(), &SPRITES[1..]);
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+
+    assert_eq!(rom.as_bytes(), &original[..]);
+}
+
+#[test]
+fn changed_level_is_moved_to_free_space() {
+    let mut rom = rom_with_level();
+    let mut free_space = FreeSpace::scan_from(&rom, AddrPc(0), 0x00);
+
+    let mut level = Level::parse(&rom, LEVEL).unwrap();
+    let (_, layer1) = ObjectLayer::parse(&[0x01, 0x52, 0x07, 0x81, 0x23, 0x45, 0xFF]).unwrap();
+    let (_, sprites) = SpriteLayer::parse(&[0x10, 0x20, 0x31, 0x21, 0x05, 0x42, 0xFF]).unwrap();
+    level.layer1 = layer1;
+    level.layer2 = Layer2Data::Objects(level.layer1.clone());
+    level.sprite_layer = sprites;
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+
+    let layer1_addr = layer1_pointer(&rom);
+    let layer1_tag = AddrPc(rom.mapper().to_pc(AddrSnes(layer1_addr)).unwrap().0 - RATS_TAG_SIZE);
+    assert!(RatsTag::read(&rom, layer1_tag).is_some());
+    let sprite_addr = read_pointer(&rom, 0x05EC00 + 2 * LEVEL, 2) | 0x070000;
+    assert!(sprite_addr >= 0x07C000, "Sprite data at {:#X} is not in bank $07's free space", sprite_addr);
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(sprite_addr), 8)).unwrap(), &[
+        0x80, 0x10, 0x20, 0x31, 0x21, 0x05, 0x42, 0xFF
+    ]);
+
+    let reparsed = Level::parse(&rom, LEVEL).unwrap();
+    assert_eq!(reparsed.primary_header.to_bytes(), level.primary_header.to_bytes());
+    assert_eq!(reparsed.layer1.to_bytes(), level.layer1.to_bytes());
+    assert_eq!(reparsed.sprite_layer.to_bytes(), level.sprite_layer.to_bytes());
+    match reparsed.layer2 {
+        Layer2Data::Objects(objects) => assert_eq!(objects.to_bytes(), level.layer1.to_bytes()),
+        Layer2Data::Background(_) => panic!("Layer2 should contain objects"),
+    }
+
+    // Writing the level again frees the RATS-protected data it replaces.
+    let (_, layer1) = ObjectLayer::parse(&[0x81, 0x23, 0x45, 0xFF]).unwrap();
+    level.layer1 = layer1;
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+    assert!(RatsTag::read(&rom, layer1_tag).is_none());
+    assert_ne!(layer1_pointer(&rom), layer1_addr);
+}
+
+#[test]
+fn background_is_written_to_bank_0c() {
+    let mut rom = rom_with_level();
+    let mut free_space = FreeSpace::scan_from(&rom, AddrPc(0), 0x00);
+
+    // Too big to fit over the old background, so it has to be moved.
+    let compressed = lc_rle1::compress(&(0..0x20).collect::<Vec<u8>>());
+    let mut level = Level::parse(&rom, LEVEL).unwrap();
+    level.layer2 = Layer2Data::Background(BackgroundData::read_from(&compressed).unwrap());
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+
+    let l2_ptr = read_pointer(&rom, 0x05E600 + 3 * LEVEL, 3);
+    assert_eq!(l2_ptr >> 16, 0xFF);
+    assert!((l2_ptr & 0xFFFF) + 5 >= 0xC000);
+    match Level::parse(&rom, LEVEL).unwrap().layer2 {
+        Layer2Data::Background(background) => assert_eq!(background.to_bytes(), compressed),
+        Layer2Data::Objects(_) => panic!("Layer2 should be a background"),
+    }
+}
+
+#[test]
+fn shared_tagged_data_is_not_freed() {
+    let mut rom = rom_with_level();
+    let mut free_space = FreeSpace::scan_from(&rom, AddrPc(0), 0x00);
+
+    let mut level = Level::parse(&rom, LEVEL).unwrap();
+    let (_, layer1) = ObjectLayer::parse(&[0x01, 0x52, 0x07, 0x81, 0x23, 0x45, 0xFF]).unwrap();
+    level.layer1 = layer1.clone();
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+
+    let layer1_addr = layer1_pointer(&rom);
+    let layer1_tag = AddrPc(rom.mapper().to_pc(AddrSnes(layer1_addr)).unwrap().0 - RATS_TAG_SIZE);
+    let pointer_bytes = (layer1_addr as u32).to_le_bytes();
+    rom.write_snes(AddrSnes(0x05E000 + 3 * (LEVEL + 1)), &pointer_bytes[..3]).unwrap();
+
+    let (_, changed) = ObjectLayer::parse(&[0x81, 0x23, 0x45, 0xFF]).unwrap();
+    level.layer1 = changed;
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+    assert_ne!(layer1_pointer(&rom), layer1_addr);
+    assert!(RatsTag::read(&rom, layer1_tag).is_some());
+    let data_begin = SnesSlice::new(AddrSnes(layer1_addr + 5), layer1.to_bytes().len());
+    assert_eq!(rom.slice_snes(data_begin).unwrap(), &layer1.to_bytes()[..]);
+}
+
+/// Like a vanilla ROM, banks $07 and $0C have no free space left.
+fn rom_with_full_banks() -> Rom {
+    let mut rom = rom_with_level();
+    rom.write_snes(AddrSnes(0x07C000), &[0xAA; 0x4000]).unwrap();
+    rom.write_snes(AddrSnes(0x0CC000), &[0xAA; 0x4000]).unwrap();
+    rom
+}
+
+#[test]
+fn sprites_are_edited_in_place_without_free_space() {
+    let mut rom = rom_with_full_banks();
+    let mut free_space = FreeSpace::scan_from(&rom, AddrPc(0), 0x00);
+
+    let mut level = Level::parse(&rom, LEVEL).unwrap();
+    let (_, sprites) = SpriteLayer::parse(&[0x21, 0x05, 0x42, 0xFF]).unwrap();
+    level.sprite_layer = sprites;
+    level.layer2 = Layer2Data::Background(BackgroundData::read_from(&[0x83, 7, 0xFF, 0xFF]).unwrap());
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+
+    assert_eq!(read_pointer(&rom, 0x05EC00 + 2 * LEVEL, 2), 0x8100);
+    assert_eq!(read_pointer(&rom, 0x05E600 + 3 * LEVEL, 3), 0xFF8000);
+    let reparsed = Level::parse(&rom, LEVEL).unwrap();
+    assert_eq!(reparsed.sprite_layer.to_bytes(), [0x21, 0x05, 0x42, 0xFF]);
+    match reparsed.layer2 {
+        Layer2Data::Background(background) => assert_eq!(background.to_bytes(), [0x83, 7, 0xFF, 0xFF]),
+        Layer2Data::Objects(_) => panic!("Layer2 should be a background"),
+    }
+
+    // More sprites than before need space in bank $07, which there isn't.
+    let (_, sprites) = SpriteLayer::parse(&[0x10, 0x20, 0x31, 0x21, 0x05, 0x42, 0xFF]).unwrap();
+    level.sprite_layer = sprites;
+    assert!(matches!(level.write(&mut rom, &mut free_space, LEVEL), Err(LevelWriteError::SpriteAllocate(_))));
+}
+
+#[test]
+fn shared_sprites_are_not_overwritten() {
+    let mut rom = rom_with_level();
+    rom.write_snes(AddrSnes(0x05EC00 + 2 * (LEVEL + 1)), &[0x00, 0x81]).unwrap();
+    let mut free_space = FreeSpace::scan_from(&rom, AddrPc(0), 0x00);
+
+    let mut level = Level::parse(&rom, LEVEL).unwrap();
+    let (_, sprites) = SpriteLayer::parse(&[0x21, 0x05, 0x42, 0xFF]).unwrap();
+    level.sprite_layer = sprites;
+    level.write(&mut rom, &mut free_space, LEVEL).unwrap();
+
+    assert_ne!(read_pointer(&rom, 0x05EC00 + 2 * LEVEL, 2), 0x8100);
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0x078100), SPRITES.len())).unwrap(), SPRITES);
+}
+
+#[test]
+fn header_setters_touch_only_their_bits() {
+    let mut header = PrimaryHeader::new(&[0xFF; 5]);
+    header.set_music(Music::Castle);
+    header.set_level_length(0x0A).unwrap();
+    header.set_timer(Timer::Seconds200);
+    assert_eq!(header.to_bytes(), [0xEA, 0xFF, 0xCF, 0x7F, 0xFF]);
+    assert_eq!(header.music(), Music::Castle);
+    assert_eq!(header.timer().seconds(), 200);
+    assert_eq!(header.level_mode(), LevelMode::HorizontalTranslucentLayer2);
+
+    header.set_level_mode(LevelMode::Vertical).unwrap();
+    assert_eq!(header.to_bytes()[1], 0xE7);
+    assert!(matches!(
+        header.set_level_mode(LevelMode::from(0x20)),
+        Err(LevelHeaderError::OutOfRange { field: "level_mode", value: 0x20, max: 0x1F })
+    ));
+    assert!(header.set_palette_fg(8).is_err());
+    assert_eq!(header.palette_fg(), 7);
+}
+
+#[test]
+fn header_builders_validate_fields() {
+    let header = PrimaryHeaderBuilder {
+        level_length: 0x13,
+        level_mode: LevelMode::from(0x0A),
+        music: Music::GhostHouse,
+        item_memory: ItemMemory::Disabled,
+        vertical_scroll: VerticalScroll::Disabled,
+        fg_bg_gfx: 0x0E,
+        ..Default::default()
+    }
+    .build()
+    .unwrap();
+    assert_eq!(header.to_bytes(), [0x13, 0x0A, 0x50, 0x00, 0xDE]);
+    assert_eq!(header.level_mode(), LevelMode::from(0x0A));
+    assert_ne!(LevelMode::from(0x0A), LevelMode::from(0x0D));
+    // Values with a name never end up in `Other`.
+    assert_eq!(LevelMode::from(0x07), LevelMode::Vertical);
+    assert!((0..=0x1F).all(|mode| u8::from(LevelMode::from(mode)) == mode));
+
+    let secondary =
+        SecondaryHeaderBuilder { main_entrance_xy_pos: (3, 0x0B), vertical_level: true, ..Default::default() }
+            .build()
+            .unwrap();
+    assert_eq!(secondary.main_entrance_xy_pos(), (3, 0x0B));
+    assert!(secondary.vertical_level());
+    assert!(SecondaryHeaderBuilder { main_entrance_xy_pos: (8, 0), ..Default::default() }.build().is_err());
+
+    let sprite_header = SpriteHeaderBuilder { sprite_memory: 0x0A, ..Default::default() }.build().unwrap();
+    assert_eq!(sprite_header.to_byte(), 0x0A);
+    assert!(SpriteHeaderBuilder { sprite_memory: 0x40, ..Default::default() }.build().is_err());
+}
@@ -0,0 +1,110 @@
+use smwe_rom::{
+    error::Map16Error,
+    graphics::map16::{
+        Map16Block,
+        Map16Tables,
+        Map16Tile,
+        BACKGROUND_MAP16,
+        FOREGROUND_MAP16,
+        TILESET_MAP16_POINTERS,
+        TILESET_SPECIFIC_BLOCK_COUNT,
+    },
+    snes_utils::{addr::AddrSnes, rom::Rom, rom_slice::SnesSlice},
+};
+
+fn rom_with_map16() -> Rom {
+    let mut rom = Rom::new(vec![0; 0x80000]).expect("Invalid ROM size");
+    // Block 025: transparent tiles, block 130: a cement block using palette 6.
+    rom.write_snes(AddrSnes(0x0D8000 + 0x25 * 8), &[0xF8, 0x00, 0xF8, 0x00, 0xF8, 0x00, 0xF8, 0x00]).unwrap();
+    rom.write_snes(AddrSnes(0x0D8000 + 0x130 * 8), &[0x8A, 0x18, 0x9A, 0x18, 0x8B, 0x18, 0x9B, 0x18]).unwrap();
+    rom.write_snes(AddrSnes(0x0D9100 + 0x1FF * 8), &[0x01, 0xC2, 0x02, 0x20, 0x03, 0x40, 0x04, 0x80]).unwrap();
+    // Tileset-specific blocks: each tileset has its own, except 0x0E which shares those of 0x0D.
+    for tileset in 0..0x0F {
+        let ptr = 0xB000 + TILESET_SPECIFIC_BLOCK_COUNT * 8 * tileset.min(0x0D);
+        rom.write_snes(TILESET_MAP16_POINTERS.begin + 2 * tileset, &(ptr as u16).to_le_bytes()).unwrap();
+        // The first tile of block 073 holds the tileset number.
+        rom.write_snes(AddrSnes(0x0D0000 | ptr), &[tileset.min(0x0D) as u8, 0x00]).unwrap();
+    }
+    rom
+}
+
+#[test]
+fn tile_fields() {
+    let tile = Map16Tile(0b1110_1011_0101_1010);
+    assert_eq!(tile.tile_number(), 0x35A);
+    assert_eq!(tile.palette(), 2);
+    assert!(tile.priority() && tile.flip_x() && tile.flip_y());
+
+    let mut tile = Map16Tile::new(0x18A, 6, false, true, false).unwrap();
+    assert_eq!(tile.0, 0x598A);
+    tile.set_palette(1).unwrap();
+    tile.set_flip_x(false);
+    tile.set_flip_y(true);
+    assert_eq!(tile.0, 0x858A);
+    assert!(matches!(tile.set_tile_number(0x400), Err(Map16Error::OutOfRange { field: "tile_number", .. })));
+    assert!(tile.set_palette(8).is_err());
+    assert_eq!(tile.0, 0x858A);
+}
+
+#[test]
+fn parse_tables() {
+    let map16 = Map16Tables::parse(&rom_with_map16()).unwrap();
+    assert_eq!((map16.foreground.len(), map16.background.len()), (0x200, 0x200));
+    assert_eq!(map16.tileset_blocks.len(), 0x0F);
+
+    let cement = map16.foreground_block(0x130, 0x05).unwrap();
+    assert_eq!(cement.tiles().map(|tile| tile.tile_number()), [0x08A, 0x09A, 0x08B, 0x09B]);
+    assert!(cement.tiles().iter().all(|tile| tile.palette() == 6 && !tile.priority()));
+
+    let block = map16.background[0x1FF];
+    assert!(block.upper_left.flip_y() && block.upper_left.flip_x() && block.upper_left.palette() == 0);
+    assert!(block.lower_left.priority() && block.upper_right.flip_x() && block.lower_right.flip_y());
+    assert!(Map16Tables::is_tileset_specific(0x080) && !Map16Tables::is_tileset_specific(0x130));
+}
+
+#[test]
+fn tileset_specific_blocks() {
+    let map16 = Map16Tables::parse(&rom_with_map16()).unwrap();
+    assert_eq!(map16.foreground_block(0x073, 0x03).unwrap().upper_left, Map16Tile(0x03));
+    assert_eq!(map16.foreground_block(0x073, 0x0E).unwrap().upper_left, Map16Tile(0x0D));
+    assert!(map16.foreground_block(0x073, 0x0F).is_none());
+
+    assert_eq!(Map16Tables::tileset_block_index(0x0FF), Some(0x8C));
+    assert_eq!(Map16Tables::tileset_block_index(0x153), Some(0x8D));
+    assert_eq!(Map16Tables::tileset_block_index(0x16E), None);
+    assert_eq!(map16.tileset_blocks[0].len(), TILESET_SPECIFIC_BLOCK_COUNT);
+}
+
+#[test]
+fn write_back_to_rom() {
+    let mut rom = rom_with_map16();
+    let mut map16 = Map16Tables::parse(&rom).unwrap();
+    map16.write_to_rom(&mut rom).unwrap();
+    assert_eq!(rom.as_bytes(), rom_with_map16().as_bytes());
+
+    map16.foreground[0x025].lower_right.set_palette(3).unwrap();
+    map16.background[0].upper_left = Map16Tile(0x1234);
+    map16.write_to_rom(&mut rom).unwrap();
+    let block = rom.slice_snes(SnesSlice::new(AddrSnes(0x0D8000 + 0x25 * 8), 8)).unwrap();
+    assert_eq!(block, &[0xF8, 0x00, 0xF8, 0x00, 0xF8, 0x00, 0xF8, 0x0C]);
+    assert_eq!(Map16Block::parse(block).unwrap().1, map16.foreground[0x025]);
+    assert_eq!(rom.slice_snes(SnesSlice::new(BACKGROUND_MAP16.begin, 2)).unwrap(), &[0x34, 0x12]);
+
+    map16.tileset_blocks[2][0].lower_left = Map16Tile(0x0456);
+    map16.write_to_rom(&mut rom).unwrap();
+    let block = rom.slice_snes(SnesSlice::new(AddrSnes(0x0DB000 + TILESET_SPECIFIC_BLOCK_COUNT * 8 * 2), 4)).unwrap();
+    assert_eq!(block, &[0x02, 0x00, 0x56, 0x04]);
+    assert_eq!(Map16Tables::parse(&rom).unwrap().foreground_block(0x073, 0x02), Some(&map16.tileset_blocks[2][0]));
+
+    // Tilesets 0x0D and 0x0E share their blocks, so they can't differ.
+    map16.tileset_blocks[0x0E][1] = Map16Block::default();
+    map16.tileset_blocks[0x0E][1].upper_left = Map16Tile(1);
+    assert!(matches!(map16.write_to_rom(&mut rom), Err(Map16Error::SharedTilesetBlocks(0x0D, 0x0E))));
+    map16.tileset_blocks[0x0D][1] = map16.tileset_blocks[0x0E][1];
+    map16.write_to_rom(&mut rom).unwrap();
+
+    map16.foreground.pop();
+    assert!(
+        matches!(map16.write_to_rom(&mut rom), Err(Map16Error::BlockCount(slice, 0x200, 0x1FF)) if slice.begin == FOREGROUND_MAP16.begin)
+    );
+}
@@ -0,0 +1,77 @@
+use smwe_rom::level::{
+    object_catalogue::{
+        ObjectInfo,
+        ObjectSettings,
+        ObjectTileset,
+        ObjectTilesets,
+        SettingsKind,
+        STANDARD_OBJECTS,
+        TILESET_SPECIFIC_OBJECTS,
+    },
+    object_layer::NonExitInstance,
+};
+
+#[test]
+fn common_objects_are_catalogued() {
+    for tileset in ObjectTileset::ALL.iter().copied() {
+        for std_obj_num in STANDARD_OBJECTS {
+            let info = ObjectInfo::standard(std_obj_num, tileset);
+            if TILESET_SPECIFIC_OBJECTS.contains(&std_obj_num) {
+                assert!(info.is_none(), "Standard object {:#04X} in {:?} is catalogued", std_obj_num, tileset);
+            } else {
+                assert!(
+                    info.map(|info| info.tilesets) == Some(ObjectTilesets::ALL),
+                    "Missing standard object {:#04X} in {:?}",
+                    std_obj_num,
+                    tileset
+                );
+            }
+        }
+        assert!(ObjectInfo::standard(0x00, tileset).is_none());
+        assert!(ObjectInfo::standard(0x40, tileset).is_none());
+    }
+
+    let coins = ObjectInfo::standard(0x05, ObjectTileset::Castle).unwrap();
+    assert_eq!((coins.name, coins.settings), ("Coins", SettingsKind::Size));
+    let pipe = ObjectInfo::standard(0x0F, ObjectTileset::Normal).unwrap();
+    assert_eq!((pipe.name, pipe.settings), ("Vertical pipe", SettingsKind::TypeAndHeight));
+    let ledge = ObjectInfo::standard(0x21, ObjectTileset::Rope).unwrap();
+    assert_eq!((ledge.name, ledge.settings), ("Long ground ledge", SettingsKind::LongWidth));
+}
+
+#[test]
+fn extended_objects_are_catalogued() {
+    assert_eq!(ObjectInfo::extended(0x10).unwrap().name, "3-Up moon");
+    assert_eq!(ObjectInfo::extended(0x13).unwrap().name, "Invisible 1-Up point 3");
+    assert_eq!(ObjectInfo::extended(0x15).unwrap().name, "Red berry");
+    assert_eq!(ObjectInfo::extended(0x17).unwrap().tilesets, ObjectTilesets::ALL);
+    assert!(ObjectInfo::extended(0x02).is_none());
+    assert!(ObjectInfo::extended(0x40).is_none());
+}
+
+#[test]
+fn settings_are_decoded() {
+    assert_eq!(SettingsKind::Size.decode(0x12), ObjectSettings::Size { width: 3, height: 2 });
+    assert_eq!(SettingsKind::Width.decode(0xF4), ObjectSettings::Width(5));
+    assert_eq!(SettingsKind::LongWidth.decode(0xFF), ObjectSettings::Width(0x100));
+    assert_eq!(SettingsKind::TypeAndHeight.decode(0x31), ObjectSettings::TypeAndHeight { variant: 3, height: 2 });
+}
+
+#[test]
+fn objects_are_described() {
+    let coins = NonExitInstance::new_standard((0, 0), 0x05, 0x12).unwrap();
+    assert_eq!(ObjectInfo::describe(&coins, ObjectTileset::Normal), "Coins (width 3, height 2)");
+    let moon = NonExitInstance::new_extended((0, 0), 0x10).unwrap();
+    assert_eq!(ObjectInfo::describe(&moon, ObjectTileset::Normal), "3-Up moon");
+    let unknown = NonExitInstance::new_extended((0, 0), 0xFE).unwrap();
+    assert_eq!(ObjectInfo::describe(&unknown, ObjectTileset::Normal), "Extended object 0xFE");
+    let tileset_specific = NonExitInstance::new_standard((0, 0), 0x29, 0x04).unwrap();
+    assert_eq!(ObjectInfo::describe(&tileset_specific, ObjectTileset::Castle), "Standard object 0x29 (settings 0x04)");
+}
+
+#[test]
+fn tilesets_map_to_object_sets() {
+    assert_eq!(ObjectTileset::from_fg_bg_gfx(0x00), Some(ObjectTileset::Normal));
+    assert_eq!(ObjectTileset::from_fg_bg_gfx(0x0D), Some(ObjectTileset::GhostHouse));
+    assert_eq!(ObjectTileset::from_fg_bg_gfx(0x0F), None);
+}
@@ -0,0 +1,138 @@
+use smwe_rom::{
+    error::ObjectError,
+    level::object_layer::{
+        relative_pos,
+        ExitInstance,
+        NonExitInstance,
+        ObjectInstance,
+        ObjectLayer,
+        ScreenJumpInstance,
+    },
+};
+
+fn standard(x: u8, y: u8, std_obj_num: u8) -> ObjectInstance {
+    ObjectInstance::NonExit(NonExitInstance::new_standard((x, y), std_obj_num, 0x00).unwrap())
+}
+
+fn layer(objects: &[(u8, ObjectInstance)]) -> ObjectLayer {
+    let mut layer = ObjectLayer::default();
+    for (screen, object) in objects.iter().cloned() {
+        layer.push(screen, object).unwrap();
+    }
+    layer
+}
+
+#[test]
+fn constructors_encode_fields() {
+    let object = NonExitInstance::new_standard((0x3, 0x1A), 0x25, 0x42).unwrap();
+    assert_eq!(object.xy_pos(), (0x3, 0x1A));
+    assert_eq!(object.std_obj_num(), 0x25);
+    assert_eq!(object.settings(), 0x42);
+    assert!(!object.new_screen());
+
+    let object = NonExitInstance::new_extended((0xF, 0x00), 0x98).unwrap();
+    assert_eq!(object.ext_obj_num(), Some(0x98));
+
+    let exit = ExitInstance::new(0x1C, true, 0x1C8).unwrap();
+    assert_eq!((exit.screen_number(), exit.secondary_exit(), exit.destination_level()), (0x1C, true, 0x1C8));
+    assert_eq!(ScreenJumpInstance::new(0x11).unwrap().screen_number(), 0x11);
+
+    assert!(matches!(NonExitInstance::new_standard((0, 0), 0x00, 0), Err(ObjectError::ReservedStandardObject)));
+    assert!(matches!(NonExitInstance::new_standard((0, 0), 0x40, 0), Err(ObjectError::OutOfRange { .. })));
+    assert!(matches!(NonExitInstance::new_standard((0x10, 0), 0x01, 0), Err(ObjectError::OutOfRange { .. })));
+    assert!(matches!(NonExitInstance::new_extended((0, 0), 0x01), Err(ObjectError::ReservedExtendedObject(1))));
+    assert!(ExitInstance::new(0, false, 0x200).is_err());
+    assert!(ScreenJumpInstance::new(0x20).is_err());
+}
+
+#[test]
+fn new_screen_flags_follow_screens() {
+    let layer = layer(&[(0, standard(1, 2, 0x05)), (1, standard(3, 4, 0x06)), (1, standard(5, 6, 0x07))]);
+    assert_eq!(layer.to_bytes(), [0x02, 0x51, 0x00, 0x84, 0x63, 0x00, 0x06, 0x75, 0x00, 0xFF]);
+    assert_eq!(layer.screens(), [0, 1, 1]);
+
+    let (_, parsed) = ObjectLayer::parse(&layer.to_bytes()).unwrap();
+    assert_eq!(parsed.len(), 3);
+    assert!(parsed.iter().all(|object| matches!(object, ObjectInstance::NonExit(_))));
+}
+
+#[test]
+fn skipped_screens_get_a_screen_jump() {
+    let mut layer = layer(&[(0, standard(1, 2, 0x05)), (4, standard(3, 4, 0x06))]);
+    assert_eq!(layer.screens(), [0, 4, 4]);
+    assert!(matches!(layer.get(1), Some(ObjectInstance::ScreenJump(_))));
+
+    layer.push(2, ObjectInstance::Exit(ExitInstance::new(2, false, 0x105).unwrap())).unwrap();
+    layer.push(5, standard(0, 0, 0x01)).unwrap();
+    assert_eq!(layer.screens(), [0, 4, 4, 2, 5]);
+}
+
+#[test]
+fn edits_keep_other_objects_on_their_screens() {
+    let mut layer = layer(&[(0, standard(1, 2, 0x05)), (1, standard(3, 4, 0x06)), (1, standard(5, 6, 0x07))]);
+
+    let removed = layer.remove(1).unwrap();
+    assert!(matches!(removed, ObjectInstance::NonExit(o) if o.std_obj_num() == 0x06));
+    assert_eq!(layer.screens(), [0, 1]);
+
+    layer.insert(0, 0, standard(0, 0, 0x01)).unwrap();
+    assert_eq!(layer.screens(), [0, 0, 1]);
+
+    layer.set_screen(1, 1).unwrap();
+    assert_eq!(layer.screens(), [0, 1, 1]);
+
+    // Going back to screen 0 takes a screen jump.
+    layer.move_object(2, 0).unwrap();
+    assert_eq!(layer.screens(), [1, 0, 0, 1]);
+    assert!(matches!(layer.get(1), Some(ObjectInstance::ScreenJump(_))));
+    assert!(matches!(layer.get(0), Some(ObjectInstance::NonExit(o)) if o.std_obj_num() == 0x07));
+    assert_eq!(layer.len(), 4);
+
+    assert!(matches!(layer.remove(4), Err(ObjectError::IndexOutOfBounds(4, 4))));
+    assert!(matches!(layer.move_object(0, 7), Err(ObjectError::IndexOutOfBounds(7, 4))));
+    assert!(matches!(layer.insert(6, 0, layer.get(0).unwrap().clone()), Err(ObjectError::IndexOutOfBounds(6, 4))));
+    assert!(layer.set_screen(0, 0x20).is_err());
+}
+
+#[test]
+fn standard_objects_with_zero_settings_are_not_exits() {
+    let (_, layer) = ObjectLayer::parse(&[0x01, 0x12, 0x00, 0x00, 0x00, 0x00, 0x05, 0xFF]).unwrap();
+    assert_eq!(layer.len(), 2);
+    assert!(matches!(layer.get(0), Some(ObjectInstance::NonExit(_))));
+    assert!(matches!(layer.get(1), Some(ObjectInstance::Exit(e)) if e.destination_level() == 0x05));
+}
+
+#[test]
+fn absolute_positions_in_horizontal_levels() {
+    let exit = ObjectInstance::Exit(ExitInstance::new(3, false, 0x105).unwrap());
+    let layer =
+        layer(&[(0, standard(1, 0x19, 0x05)), (1, standard(0xF, 2, 0x06)), (3, standard(4, 5, 0x07)), (3, exit)]);
+
+    let positions: Vec<_> = layer.positioned_objects(false).map(|o| (o.index, o.screen, o.block_pos)).collect();
+    assert_eq!(positions, [
+        (0, 0, Some((0x01, 0x19))),
+        (1, 1, Some((0x1F, 0x02))),
+        (2, 3, None),
+        (3, 3, Some((0x34, 0x05))),
+        (4, 3, None),
+    ]);
+
+    assert_eq!(relative_pos((0x34, 0x05), false).unwrap(), (3, (4, 5)));
+    assert!(relative_pos((0, 27), false).is_err());
+    assert!(relative_pos((0x200, 0), false).is_err());
+}
+
+#[test]
+fn absolute_positions_in_vertical_levels() {
+    let layer = layer(&[(0, standard(1, 0x02, 0x05)), (2, standard(3, 0x14, 0x06))]);
+
+    let positions: Vec<_> = layer.positioned_objects(true).filter_map(|o| o.block_pos).collect();
+    assert_eq!(positions, [(0x01, 0x02), (0x13, 0x24)]);
+
+    for &pos in &positions {
+        let (screen, xy_pos) = relative_pos(pos, true).unwrap();
+        let object = NonExitInstance::new_standard(xy_pos, 0x01, 0).unwrap();
+        assert_eq!(object.absolute_pos(screen, true), pos);
+    }
+    assert!(relative_pos((32, 0), true).is_err());
+}
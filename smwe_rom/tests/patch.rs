@@ -0,0 +1,280 @@
+#[macro_use]
+extern crate quickcheck;
+
+use smwe_rom::{
+    crc32::crc32,
+    error::{BpsError, IpsError},
+    patch::{bps, bps::BpsPatch, ips, ips::Offsets},
+    snes_utils::{addr::AddrPc, rom::Rom},
+};
+
+fn rom_from(bytes: &[u8], size: usize) -> Rom {
+    let mut data = vec![0; size];
+    data[..bytes.len()].copy_from_slice(bytes);
+    Rom::new(data).expect("Invalid ROM size")
+}
+
+fn ips_round_trip(source: &Rom, target: &Rom) -> Vec<u8> {
+    let patch = ips::create(source, target).expect("Failed to create IPS patch");
+    let mut patched = Rom::new(source.as_bytes().to_vec()).unwrap();
+    ips::apply(&mut patched, &patch, Offsets::Headerless).expect("Failed to apply IPS patch");
+    assert_eq!(patched.as_bytes(), target.as_bytes());
+    patch
+}
+
+fn bps_round_trip(source: &Rom, target: &Rom) -> Vec<u8> {
+    let patch = bps::create(source, target, "");
+    let mut patched = Rom::new(source.as_bytes().to_vec()).unwrap();
+    bps::apply(&mut patched, &patch).expect("Failed to apply BPS patch");
+    assert_eq!(patched.as_bytes(), target.as_bytes());
+    patch
+}
+
+#[test]
+fn ips_apply_records() {
+    let mut rom = rom_from(&[], 0x400);
+    #[rustfmt::skip]
+    let patch = [
+        b'P', b'A', b'T', b'C', b'H',
+        0x00, 0x00, 0x10, 0x00, 0x03, 1, 2, 3,
+        0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x04, 0xAA,
+        0x00, 0x04, 0x00, 0x00, 0x02, 7, 8,
+        b'E', b'O', b'F',
+    ];
+    ips::apply(&mut rom, &patch, Offsets::Headerless).unwrap();
+    assert_eq!(&rom.as_bytes()[0x10..0x13], &[1, 2, 3]);
+    assert_eq!(&rom.as_bytes()[0x20..0x25], &[0xAA, 0xAA, 0xAA, 0xAA, 0x00]);
+    assert_eq!(rom.len(), 0x402);
+    assert_eq!(&rom.as_bytes()[0x400..], &[7, 8]);
+}
+
+#[test]
+fn ips_apply_truncation() {
+    let mut rom = rom_from(&[], 0x800);
+    ips::apply(&mut rom, b"PATCHEOF\x00\x04\x00", Offsets::Headerless).unwrap();
+    assert_eq!(rom.len(), 0x400);
+}
+
+#[test]
+fn ips_apply_truncation_never_extends() {
+    let mut rom = rom_from(&[], 0x400);
+    ips::apply(&mut rom, b"PATCHEOF\x00\x08\x00", Offsets::Headerless).unwrap();
+    assert_eq!(rom.len(), 0x400);
+}
+
+#[test]
+fn ips_apply_past_16_mib() {
+    let mut rom = rom_from(&[], 0x400);
+    let patch = b"PATCH\xFF\xFF\xF0\x00\x00\x00\x20\xAAEOF";
+    assert!(matches!(ips::apply(&mut rom, patch, Offsets::Headerless), Err(IpsError::TooLarge(0x1000010))));
+    assert_eq!(rom.len(), 0x400);
+}
+
+#[test]
+fn ips_apply_malformed() {
+    let mut rom = rom_from(&[], 0x400);
+    let mut apply = |patch: &[u8]| ips::apply(&mut rom, patch, Offsets::Headerless);
+    assert!(matches!(apply(b"PACH"), Err(IpsError::MissingHeader)));
+    assert!(matches!(apply(b"PATCH\x00\x00\x10\x00\x05\x01"), Err(IpsError::UnexpectedEnd(10))));
+    assert!(matches!(apply(b"PATCH\x00\x00"), Err(IpsError::UnexpectedEnd(5))));
+    assert!(matches!(apply(b"PATCHEOF\x01"), Err(IpsError::TrailingData(8))));
+}
+
+#[test]
+fn ips_apply_is_all_or_nothing() {
+    let mut rom = rom_from(&[], 0x400);
+    let patch = b"PATCH\x00\x00\x10\x00\x02\x01\x02\x00\x00\x20\x00\x05\x01";
+    assert!(matches!(ips::apply(&mut rom, patch, Offsets::Headerless), Err(IpsError::UnexpectedEnd(17))));
+    assert_eq!(rom.as_bytes(), &[0; 0x400][..]);
+    assert!(rom.modified_regions().is_empty());
+}
+
+#[test]
+fn ips_apply_headered_offsets() {
+    let mut data = vec![0; 0x200];
+    data.extend_from_slice(&[0; 0x400]);
+    let mut rom = Rom::new(data).unwrap();
+    let patch = b"PATCH\x00\x00\x01\x00\x01\x07\x00\x02\x10\x00\x02\x01\x02EOF";
+    ips::apply(&mut rom, patch, Offsets::Headered).unwrap();
+    assert_eq!(rom.smc_header().unwrap()[1], 7);
+    assert_eq!(&rom.as_bytes()[0x10..0x12], &[1, 2]);
+
+    let mut rom = rom_from(&[], 0x400);
+    ips::apply(&mut rom, patch, Offsets::Headerless).unwrap();
+    assert_eq!(&rom.as_bytes()[0x210..0x212], &[1, 2]);
+    assert!(matches!(ips::apply(&mut rom, b"PATCHEOF\x00\x01\x00", Offsets::Headered), Err(IpsError::TooSmall(0x100))));
+}
+
+#[test]
+fn ips_create_uses_rle_and_merges_records() {
+    let source = rom_from(&[], 0x400);
+    let mut target = rom_from(&[], 0x400);
+    target.write_pc(AddrPc(0x10), &[1, 0, 0, 2]).unwrap();
+    target.write_pc(AddrPc(0x100), &[0x55; 0x80]).unwrap();
+    let patch = ips_round_trip(&source, &target);
+    #[rustfmt::skip]
+    assert_eq!(patch, [
+        b'P', b'A', b'T', b'C', b'H',
+        0x00, 0x00, 0x10, 0x00, 0x04, 1, 0, 0, 2,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x80, 0x55,
+        b'E', b'O', b'F',
+    ]);
+}
+
+#[test]
+fn ips_create_avoids_footer_offset() {
+    let source = rom_from(&[], 0x460000);
+    let mut target = rom_from(&[], 0x460000);
+    target.write_pc(AddrPc(0x454F46), &[1, 2]).unwrap();
+    let patch = ips_round_trip(&source, &target);
+    assert_eq!(&patch[5..8], &[0x45, 0x4F, 0x45]);
+}
+
+#[test]
+fn ips_create_resizes() {
+    let source = rom_from(&[1, 2, 3], 0x800);
+    let mut target = rom_from(&[1, 2, 3], 0x400);
+    let patch = ips_round_trip(&source, &target);
+    assert_eq!(&patch[patch.len() - 6..], b"EOF\x00\x04\x00");
+
+    target.resize(0xC00, 0);
+    ips_round_trip(&source, &target);
+}
+
+#[test]
+fn ips_create_too_large() {
+    let source = rom_from(&[], 0x400);
+    let target = rom_from(&[], ips::MAX_SIZE + 0x400);
+    assert!(matches!(ips::create(&source, &target), Err(IpsError::TooLarge(_))));
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    assert_eq!(crc32(&[]), 0);
+}
+
+#[test]
+fn bps_apply_every_action() {
+    let mut rom = rom_from(&[1, 2, 3, 4, 5, 6, 7, 8], 0x400);
+    let source_crc = crc32(rom.as_bytes());
+    let mut target = vec![0; 0x400];
+    target[..16].copy_from_slice(&[1, 2, 3, 4, 9, 9, 6, 7, 8, 9, 6, 7, 8, 9, 6, 7]);
+
+    #[rustfmt::skip]
+    let mut patch = vec![
+        b'B', b'P', b'S', b'1', 0x00, 0x87, 0x00, 0x87, 0x84, b'm', b'e', b't', b'a',
+        (3 << 2) | 0x80,                  // SourceRead 4
+        (1 << 2) | 1 | 0x80, 9, 9,        // TargetRead 2
+        (2 << 2) | 2 | 0x80, (5 << 1) | 0x80, // SourceCopy 3 from +5
+        (6 << 2) | 3 | 0x80, (5 << 1) | 0x80, // TargetCopy 7 from +5
+        0x3C, 0x9E,                       // SourceRead 0x3F0
+    ];
+    patch.extend_from_slice(&source_crc.to_le_bytes());
+    patch.extend_from_slice(&crc32(&target).to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+
+    let parsed = BpsPatch::parse(&patch).unwrap();
+    assert_eq!((parsed.source_size, parsed.target_size, parsed.metadata.as_str()), (0x400, 0x400, "meta"));
+    parsed.apply(&mut rom).unwrap();
+    assert_eq!(rom.as_bytes(), target.as_slice());
+    let regions: Vec<(usize, usize)> = rom.modified_regions().iter().map(|s| (s.begin.0, s.size)).collect();
+    assert_eq!(regions, [(4, 12)]);
+}
+
+#[test]
+fn bps_rejects_wrong_source() {
+    let source = rom_from(&[1, 2, 3], 0x400);
+    let target = rom_from(&[3, 2, 1], 0x400);
+    let patch = bps::create(&source, &target, "");
+
+    let mut other = rom_from(&[1, 2, 4], 0x400);
+    let err = bps::apply(&mut other, &patch).unwrap_err();
+    assert!(matches!(err, BpsError::WrongSourceRom { expected, actual }
+        if expected == crc32(source.as_bytes()) && actual == crc32(other.as_bytes())));
+    assert_eq!(&other.as_bytes()[..3], &[1, 2, 4]);
+    assert!(!other.is_modified());
+}
+
+#[test]
+fn bps_rejects_corrupted_patch() {
+    let source = rom_from(&[1, 2, 3], 0x400);
+    let target = rom_from(&[3, 2, 1], 0x400);
+    let mut patch = bps::create(&source, &target, "");
+    let mut rom = rom_from(&[1, 2, 3], 0x400);
+
+    assert!(matches!(bps::apply(&mut rom, b"BPS0"), Err(BpsError::MissingHeader)));
+    assert!(matches!(bps::apply(&mut rom, b"BPS1\x80"), Err(BpsError::UnexpectedEnd(5))));
+    patch[8] ^= 1;
+    assert!(matches!(bps::apply(&mut rom, &patch), Err(BpsError::PatchChecksum { .. })));
+}
+
+#[test]
+fn bps_rejects_huge_target() {
+    let mut rom = rom_from(&[], 0x400);
+    let mut patch = b"BPS1\x00\x87".to_vec();
+    // Target size 0x40000000, no metadata.
+    patch.extend_from_slice(&[0x00, 0x7F, 0x7E, 0x7E, 0x82, 0x80]);
+    patch.extend_from_slice(&crc32(rom.as_bytes()).to_le_bytes());
+    patch.extend_from_slice(&0u32.to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+
+    assert_eq!(BpsPatch::parse(&patch).unwrap().target_size, 0x40000000);
+    assert!(matches!(bps::apply(&mut rom, &patch), Err(BpsError::TargetTooLarge(0x40000000))));
+    assert!(!rom.is_modified());
+}
+
+#[test]
+fn bps_create_uses_copies() {
+    let mut source = rom_from(&[], 0x10000);
+    let mut data: Vec<u8> = (0..0x1000).map(|i| (i * 7 % 256) as u8).collect();
+    source.write_pc(AddrPc(0x1000), &data).unwrap();
+    let mut target = rom_from(&[], 0x10000);
+    target.write_pc(AddrPc(0x8000), &data).unwrap();
+    data.reverse();
+    target.write_pc(AddrPc(0xA000), &data).unwrap();
+    target.write_pc(AddrPc(0xB000), &data).unwrap();
+    let patch = bps_round_trip(&source, &target);
+    assert!(patch.len() < 0x1100, "Patch is too large: {:#x} bytes", patch.len());
+}
+
+quickcheck! {
+    fn bps_round_trips_arbitrary_changes(changes: Vec<(u16, Vec<u8>)>, new_size: u16) -> bool {
+        let source = rom_from(&[], 0x10000);
+        let mut target = rom_from(&[], 0x10000);
+        for (offset, bytes) in changes {
+            let offset = offset as usize;
+            let end = (offset + bytes.len()).min(0x10000);
+            target.write_pc(AddrPc(offset), &bytes[..end - offset]).unwrap();
+        }
+        target.resize(new_size as usize * 2, 0xFF);
+        bps_round_trip(&source, &target);
+        true
+    }
+
+    fn bps_apply_never_panics(data: Vec<u8>) -> bool {
+        let mut patch = b"BPS1\x88\x80\x80".to_vec();
+        patch.extend_from_slice(&data);
+        patch.extend_from_slice(&[0; 8]);
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        let mut rom = rom_from(&[], 0x400);
+        let _ = bps::apply(&mut rom, &patch);
+        true
+    }
+
+    fn ips_round_trips_arbitrary_changes(changes: Vec<(u16, Vec<u8>)>, new_size: u16) -> bool {
+        let source = rom_from(&[], 0x10000);
+        let mut target = rom_from(&[], 0x10000);
+        for (offset, bytes) in changes {
+            let offset = offset as usize;
+            let end = (offset + bytes.len()).min(0x10000);
+            target.write_pc(AddrPc(offset), &bytes[..end - offset]).unwrap();
+        }
+        target.resize(new_size as usize * 2, 0xFF);
+        ips_round_trip(&source, &target);
+        true
+    }
+}
@@ -0,0 +1,195 @@
+use smwe_rom::{
+    error::{ExpansionError, RomError},
+    snes_utils::{
+        addr::{AddrPc, AddrSnes, Sa1BankMapping},
+        free_space::FreeSpace,
+        mapper::Mapper,
+        rom::Rom,
+        rom_slice::{PcSlice, SnesSlice},
+    },
+    RomInternalHeader,
+};
+
+fn blank_rom(size: usize) -> Rom {
+    Rom::new(vec![0; size]).expect("Invalid ROM size")
+}
+
+#[test]
+fn write_and_read_back() {
+    let mut rom = blank_rom(0x80000);
+    rom.write_pc(AddrPc(0x10), &[1, 2, 3]).unwrap();
+    rom.write_lorom(AddrSnes(0x018000), &[4, 5]).unwrap();
+    rom.write_hirom(AddrSnes(0xC20000), &[6]).unwrap();
+
+    assert_eq!(rom.slice_pc(PcSlice::new(AddrPc(0x10), 3)).unwrap(), &[1, 2, 3]);
+    assert_eq!(rom.slice_pc(PcSlice::new(AddrPc(0x8000), 2)).unwrap(), &[4, 5]);
+    assert_eq!(rom.slice_lorom(SnesSlice::new(AddrSnes(0x018000), 2)).unwrap(), &[4, 5]);
+    assert_eq!(rom.slice_pc(PcSlice::new(AddrPc(0x20000), 1)).unwrap(), &[6]);
+
+    rom.slice_lorom_mut(SnesSlice::new(AddrSnes(0x008010), 3)).unwrap()[1] = 7;
+    assert_eq!(rom.slice_pc(PcSlice::new(AddrPc(0x10), 3)).unwrap(), &[1, 7, 3]);
+}
+
+#[test]
+fn invalid_writes() {
+    let mut rom = blank_rom(0x80000);
+    assert!(matches!(rom.write_pc(AddrPc(0x7FFFF), &[1, 2]), Err(RomError::SlicePc(_))));
+    assert!(matches!(rom.write_lorom(AddrSnes(0x7E0000), &[1]), Err(RomError::AddressSliceLoRom(_))));
+    assert!(matches!(rom.write_lorom(AddrSnes(0x208000), &[1]), Err(RomError::SliceLoRom(_))));
+    assert!(matches!(rom.write_hirom(AddrSnes(0x000000), &[1]), Err(RomError::AddressSliceHiRom(_))));
+    assert!(matches!(rom.slice_hirom_mut(SnesSlice::new(AddrSnes(0xC80000), 1)), Err(RomError::SliceHiRom(_))));
+    assert!(!rom.is_modified());
+}
+
+#[test]
+fn modified_regions_are_merged() {
+    let mut rom = blank_rom(0x80000);
+    rom.write_pc(AddrPc(0x100), &[0; 0x10]).unwrap();
+    rom.write_pc(AddrPc(0x200), &[0; 0x10]).unwrap();
+    rom.write_pc(AddrPc(0x110), &[0; 0x08]).unwrap();
+    rom.write_pc(AddrPc(0x1F8), &[0; 0x10]).unwrap();
+    rom.write_pc(AddrPc(0x300), &[]).unwrap();
+
+    let regions: Vec<(usize, usize)> = rom.modified_regions().iter().map(|s| (s.begin.0, s.size)).collect();
+    assert_eq!(regions, [(0x100, 0x18), (0x1F8, 0x18)]);
+
+    rom.write_pc(AddrPc(0x118), &[0; 0xE0]).unwrap();
+    let regions: Vec<(usize, usize)> = rom.modified_regions().iter().map(|s| (s.begin.0, s.size)).collect();
+    assert_eq!(regions, [(0x100, 0x110)]);
+
+    rom.clear_modified_regions();
+    assert!(rom.modified_regions().is_empty());
+}
+
+fn rom_with_header(size: usize, header_pc: usize, map_mode: u8, rom_type: u8) -> Rom {
+    let mut data = vec![0; size];
+    data[header_pc + 0x15] = map_mode;
+    data[header_pc + 0x16] = rom_type;
+    data[header_pc + 0x1C..header_pc + 0x20].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    data[0x10] = 0xAB;
+    Rom::new(data).expect("Invalid ROM size")
+}
+
+#[test]
+fn mapper_detection() {
+    assert_eq!(blank_rom(0x80000).mapper(), Mapper::LoRom);
+
+    let rom = rom_with_header(0x80000, 0x7FC0, 0x30, 0x02);
+    assert_eq!(rom.mapper(), Mapper::LoRom);
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0x808010), 1)).unwrap(), &[0xAB]);
+
+    let rom = rom_with_header(0x80000, 0xFFC0, 0x21, 0x02);
+    assert_eq!(rom.mapper(), Mapper::HiRom);
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0xC00010), 1)).unwrap(), &[0xAB]);
+
+    let rom = rom_with_header(0x80000, 0x7FC0, 0x23, 0x35);
+    assert_eq!(rom.mapper(), Mapper::Sa1(Sa1BankMapping::default()));
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0xC00010), 1)).unwrap(), &[0xAB]);
+    assert!(matches!(rom.slice_snes(SnesSlice::new(AddrSnes(0x400000), 1)), Err(RomError::AddressSliceSnes(_))));
+
+    let rom = rom_with_header(0x410000, 0x40FFC0, 0x35, 0x02);
+    assert_eq!(rom.mapper(), Mapper::ExHiRom);
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0xC00010), 1)).unwrap(), &[0xAB]);
+}
+
+#[test]
+fn map_mode_kinds() {
+    let sa1 = RomInternalHeader::parse(&rom_with_header(0x80000, 0x7FC0, 0x23, 0x35)).unwrap().map_mode;
+    assert!(sa1.is_sa1());
+    assert!(!sa1.is_lorom() && !sa1.is_hirom() && !sa1.is_exlorom() && !sa1.is_exhirom());
+
+    let exhirom = RomInternalHeader::parse(&rom_with_header(0x410000, 0x40FFC0, 0x25, 0x02)).unwrap().map_mode;
+    assert!(exhirom.is_exhirom() && exhirom.is_slow());
+    assert!(!exhirom.is_hirom() && !exhirom.is_lorom());
+}
+
+#[test]
+fn write_through_mapper() {
+    let mut rom = blank_rom(0x80000);
+    rom.set_mapper(Mapper::HiRom);
+    rom.write_snes(AddrSnes(0xC10000), &[1, 2]).unwrap();
+    assert_eq!(rom.slice_pc(PcSlice::new(AddrPc(0x010000), 2)).unwrap(), &[1, 2]);
+    assert!(matches!(rom.write_snes(AddrSnes(0xC80000), &[1]), Err(RomError::SliceSnes(_))));
+}
+
+#[test]
+fn smc_header_is_preserved() {
+    let mut data = vec![0xAA; 0x200];
+    data.extend_from_slice(&[1; 0x4000]);
+    let rom = Rom::new(data.clone()).unwrap();
+    assert!(rom.has_smc_header());
+    assert_eq!(rom.smc_header(), Some(&[0xAA; 0x200][..]));
+    assert_eq!(rom.len(), 0x4000);
+    assert_eq!(rom.to_original_bytes(), data);
+    assert_eq!(rom.to_bytes(false), &data[0x200..]);
+}
+
+#[test]
+fn smc_header_is_generated() {
+    let rom = Rom::new(vec![1; 0x4000]).unwrap();
+    assert!(!rom.has_smc_header());
+    assert_eq!(rom.to_original_bytes(), vec![1; 0x4000]);
+    let headered = rom.to_bytes(true);
+    assert_eq!(headered.len(), 0x4200);
+    assert_eq!(&headered[..3], &[0x02, 0x00, 0x00]);
+    assert_eq!(Rom::new(headered).unwrap().as_bytes(), rom.as_bytes());
+}
+
+fn lorom_with_header(size: usize) -> Rom {
+    let mut data: Vec<u8> = (0..size).map(|i| (i % 253) as u8).collect();
+    let header = &mut data[0x7FC0..0x7FE0];
+    header[..21].copy_from_slice(b"SUPER MARIOWORLD     ");
+    header[21..28].copy_from_slice(&[0x20, 0x02, 0x09, 0x01, 0x01, 0x01, 0x00]);
+    header[28..32].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+    Rom::new(data).expect("Invalid ROM size")
+}
+
+#[test]
+fn expand_within_lorom() {
+    let mut rom = lorom_with_header(0x80000);
+    rom.expand(0x200000).unwrap();
+    assert_eq!(rom.len(), 0x200000);
+    assert!(rom.as_bytes()[0x80000..].iter().all(|&b| b == 0));
+
+    let header = RomInternalHeader::parse(&rom).unwrap();
+    assert_eq!(header.rom_size, 0x0B);
+    assert_eq!(header.rom_size_in_kb(), 2048);
+    assert!(header.checksum_valid());
+    assert_eq!(rom.mapper(), Mapper::LoRom);
+}
+
+#[test]
+fn expand_to_exlorom() {
+    let mut rom = lorom_with_header(0x80000);
+    let original = rom.as_bytes().to_vec();
+    rom.expand(0x800000).unwrap();
+
+    let header = RomInternalHeader::parse(&rom).unwrap();
+    assert!(header.map_mode.is_exlorom());
+    assert_eq!(header.rom_size, 0x0D);
+    assert!(header.checksum_valid());
+    assert_eq!(rom.mapper(), Mapper::ExLoRom);
+    assert_eq!(&rom.as_bytes()[0x400000..0x480000], &rom.as_bytes()[..0x80000]);
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0x058000), 0x10)).unwrap(), &original[0x28000..0x28010]);
+}
+
+#[test]
+fn expand_to_exlorom_mirrors_whole_original() {
+    let mut rom = lorom_with_header(0x300000);
+    let original = rom.as_bytes().to_vec();
+    rom.expand(0x800000).unwrap();
+
+    assert!(RomInternalHeader::parse(&rom).unwrap().checksum_valid());
+    assert_eq!(&rom.as_bytes()[0x400000..0x700000], &rom.as_bytes()[..0x300000]);
+    assert!(rom.as_bytes()[0x700000..].iter().all(|&b| b == 0));
+    assert_eq!(rom.slice_snes(SnesSlice::new(AddrSnes(0x5F8000), 0x10)).unwrap(), &original[0x2F8000..0x2F8010]);
+    assert!(FreeSpace::scan(&rom).free_blocks().iter().all(|block| block.begin.0 + block.size <= 0x400000));
+}
+
+#[test]
+fn expand_errors() {
+    let mut rom = lorom_with_header(0x80000);
+    assert!(matches!(rom.expand(0x300000), Err(ExpansionError::Size(0x80000, 0x300000))));
+    assert!(matches!(rom.expand(0x80000), Err(ExpansionError::Size(..))));
+    assert!(matches!(blank_rom(0x80000).expand(0x100000), Err(ExpansionError::Header(_))));
+}
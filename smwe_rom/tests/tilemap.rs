@@ -0,0 +1,96 @@
+use smwe_rom::level::{
+    object_layer::{NonExitInstance, ObjectInstance, ObjectLayer},
+    tilemap::{render_objects, LevelTilemap, EMPTY_BLOCK},
+};
+
+fn standard(x: u8, y: u8, std_obj_num: u8, settings: u8) -> ObjectInstance {
+    ObjectInstance::NonExit(NonExitInstance::new_standard((x, y), std_obj_num, settings).unwrap())
+}
+
+fn layer(objects: &[(u8, ObjectInstance)]) -> ObjectLayer {
+    let mut layer = ObjectLayer::default();
+    for (screen, object) in objects.iter().cloned() {
+        layer.push(screen, object).unwrap();
+    }
+    layer
+}
+
+#[test]
+fn fill_objects_place_blocks_in_order() {
+    // 3x2 coins, then a single cement block over the bottom-right coin.
+    let layer = layer(&[(0, standard(1, 2, 0x05, 0x12)), (0, standard(3, 3, 0x0D, 0x00))]);
+    let rendered = render_objects(&layer, 2, false, 0);
+    let tilemap = &rendered.tilemap;
+
+    assert_eq!((tilemap.width, tilemap.height), (32, 27));
+    assert!(rendered.unsupported.is_empty());
+    assert_eq!(tilemap.get(1, 2), Some(0x02B));
+    assert_eq!(tilemap.get(3, 2), Some(0x02B));
+    assert_eq!(tilemap.get(3, 3), Some(0x130));
+    assert_eq!(tilemap.get(4, 2), Some(EMPTY_BLOCK));
+    assert_eq!(tilemap.get(32, 0), None);
+}
+
+#[test]
+fn objects_are_placed_on_their_screens() {
+    let layer = layer(&[(1, standard(0xF, 0x19, 0x09, 0x11)), (2, standard(0, 0, 0x09, 0x00))]);
+    let rendered = render_objects(&layer, 2, false, 0);
+
+    // The object spilling out of the level is clipped, and the one past its last screen is dropped.
+    assert_eq!(rendered.tilemap.get(0x1F, 0x1A), Some(0x11E));
+    assert_eq!(rendered.tilemap.blocks.iter().filter(|&&block| block == 0x11E).count(), 2);
+}
+
+#[test]
+fn vertical_levels_stack_screens() {
+    let tilemap = LevelTilemap::new(3, true);
+    assert_eq!((tilemap.width, tilemap.height), (32, 48));
+
+    let layer = layer(&[(2, standard(3, 0x14, 0x0E, 0x00))]);
+    let rendered = render_objects(&layer, 3, true, 0);
+    assert_eq!(rendered.tilemap.get(0x13, 0x24), Some(0x132));
+}
+
+fn extended(x: u8, y: u8, ext_obj_num: u8) -> ObjectInstance {
+    ObjectInstance::NonExit(NonExitInstance::new_extended((x, y), ext_obj_num).unwrap())
+}
+
+#[test]
+fn vertical_pipes_are_rendered() {
+    let layer = layer(&[
+        // Pipe 3 blocks high ending at the top, and an enterable one 2 blocks high ending at the bottom.
+        (0, standard(0, 2, 0x0F, 0x02)),
+        (0, standard(4, 2, 0x0F, 0x31)),
+    ]);
+    let rendered = render_objects(&layer, 1, false, 0);
+    let tilemap = &rendered.tilemap;
+    assert!(rendered.unsupported.is_empty());
+
+    let column = |x: usize, y: usize, len: usize| (y..y + len).map(|y| tilemap.get(x, y).unwrap()).collect::<Vec<_>>();
+    assert_eq!(column(0, 2, 4), [0x133, 0x135, 0x135, EMPTY_BLOCK]);
+    assert_eq!(column(1, 2, 3), [0x134, 0x136, 0x136]);
+    assert_eq!(column(4, 2, 2), [0x135, 0x137]);
+    assert_eq!(column(5, 2, 2), [0x136, 0x138]);
+}
+
+#[test]
+fn unsupported_objects_are_reported() {
+    let layer = layer(&[
+        (0, standard(0, 0, 0x05, 0x00)),
+        // Pipe type 6 has no routine in the game.
+        (0, standard(0, 5, 0x0F, 0x63)),
+        // Neither tileset-specific nor extended objects are rendered yet.
+        (0, standard(2, 5, 0x2A, 0x00)),
+        (1, extended(0, 0, 0x10)),
+        (1, standard(0, 3, 0x09, 0x00)),
+    ]);
+    let rendered = render_objects(&layer, 2, false, 0);
+    assert_eq!(rendered.unsupported, [1, 2, 3]);
+    assert_eq!(rendered.tilemap.get(0, 5), Some(EMPTY_BLOCK));
+    assert_eq!(rendered.tilemap.get(2, 5), Some(EMPTY_BLOCK));
+    assert_eq!(rendered.tilemap.get(16, 0), Some(EMPTY_BLOCK));
+    assert_eq!(rendered.tilemap.get(16, 3), Some(0x11E));
+
+    // The same goes for levels using a tileset the game doesn't have.
+    assert_eq!(render_objects(&layer, 2, false, 0x0F).unsupported, [1, 2, 3]);
+}
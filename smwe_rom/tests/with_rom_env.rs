@@ -1,11 +1,36 @@
 use std::env;
 
-use smwe_rom::SmwRom;
+use smwe_rom::{compression::lc_lz2, graphics::gfx_file::GFX_FILES_META, snes_utils::rom::Rom, SmwRom};
+
+fn rom_path() -> std::ffi::OsString {
+    let rom_path = env::var_os("ROM_PATH").expect("ROM_PATH not set");
+    assert!(std::fs::metadata(&rom_path).expect("ROM_PATH invalid").is_file());
+    rom_path
+}
 
 #[test]
 #[ignore]
 fn test_with_rom_env() {
-    let rom_path = env::var_os("ROM_PATH").expect("ROM_PATH not set");
-    assert!(std::fs::metadata(&rom_path).expect("ROM_PATH invalid").is_file());
-    SmwRom::from_file(rom_path).expect("Rom parse error encountered");
+    SmwRom::from_file(rom_path()).expect("Rom parse error encountered");
+}
+
+#[test]
+#[ignore]
+fn test_lz2_recompress_gfx_files() {
+    let rom = Rom::new(std::fs::read(rom_path()).expect("Could not read ROM")).expect("Invalid ROM");
+    for (file_num, (_, slice)) in GFX_FILES_META.iter().enumerate() {
+        let original = rom.slice_snes(*slice).expect("Invalid GFX file slice");
+        let decompressed = lc_lz2::decompress(original).expect("Could not decompress GFX file");
+        assert_eq!(decompressed.input_len, slice.size, "GFX file {:02X}", file_num);
+        let decompressed = decompressed.output;
+        let recompressed = lc_lz2::compress(&decompressed);
+        assert_eq!(decompressed, lc_lz2::decompress(&recompressed).unwrap().output, "GFX file {:02X}", file_num);
+        assert!(
+            recompressed.len() <= original.len(),
+            "GFX file {:02X} recompressed to {} bytes, originally {} bytes",
+            file_num,
+            recompressed.len(),
+            original.len()
+        );
+    }
 }
@@ -1 +1,2 @@
 pub const TEXT_ERROR: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+pub const TEXT_WARNING: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
@@ -1,5 +1,5 @@
 use imgui::{im_str, ImString, Ui, Window};
-use smwe_rom::snes_utils::addr::{Addr, AddrPc, AddrSnes};
+use smwe_rom::snes_utils::addr::{Addr, AddrPc, AddrSnes, Sa1BankMapping};
 
 use self::{helpers::*, modes::*};
 use crate::{
@@ -15,6 +15,7 @@ pub struct UiAddressConverter {
 
     conversion_mode: ConversionMode,
     include_header:  bool,
+    sa1_banks:       [i32; 4],
 
     text_pc:    ImString,
     text_snes:  ImString,
@@ -46,26 +47,46 @@ impl UiTool for UiAddressConverter {
 }
 
 impl UiAddressConverter {
-    pub fn new(id: WindowId) -> Self {
+    pub fn new(id: WindowId, include_header: bool) -> Self {
         log::info!("Opened Address Converter");
         UiAddressConverter {
-            title:           title_with_id("Address converter", id),
+            title: title_with_id("Address converter", id),
             conversion_mode: ConversionMode::LoRom,
-            include_header:  false,
-            text_pc:         ImString::new("0"),
-            text_snes:       ImString::new("8000"),
-            text_error:      ImString::new(""),
+            include_header,
+            sa1_banks: [0, 1, 2, 3],
+            text_pc: ImString::new(format!("{:x}", adjust_to_header(0, include_header))),
+            text_snes: ImString::new("8000"),
+            text_error: ImString::new(""),
         }
     }
 
     fn mode_selection(&mut self, ui: &Ui) {
-        let lorom_changed = ui.radio_button(im_str!("PC and LoROM"), &mut self.conversion_mode, ConversionMode::LoRom);
-        let hirom_changed = ui.radio_button(im_str!("PC and HiROM"), &mut self.conversion_mode, ConversionMode::HiRom);
-        if lorom_changed || hirom_changed {
+        let modes = [
+            (im_str!("PC and LoROM"), ConversionMode::LoRom),
+            (im_str!("PC and HiROM"), ConversionMode::HiRom),
+            (im_str!("PC and ExLoROM"), ConversionMode::ExLoRom),
+            (im_str!("PC and ExHiROM"), ConversionMode::ExHiRom),
+            (im_str!("PC and SA-1"), ConversionMode::Sa1),
+        ];
+        let mut mode_changed = false;
+        for (label, mode) in modes.iter() {
+            mode_changed |= ui.radio_button(label, &mut self.conversion_mode, *mode);
+        }
+        if mode_changed {
             log::info!("Conversion mode changed to {}", self.conversion_mode);
             self.update_addresses(ConvDir::PcToSnes);
         }
 
+        if self.conversion_mode == ConversionMode::Sa1
+            && ui.input_int4(im_str!("SA-1 banks ($2220-$2223)"), &mut self.sa1_banks).build()
+        {
+            for bank in self.sa1_banks.iter_mut() {
+                *bank = (*bank).clamp(0, 7);
+            }
+            log::info!("SA-1 bank mapping changed to {:?}", self.sa1_banks);
+            self.update_addresses(ConvDir::PcToSnes);
+        }
+
         if ui.checkbox(im_str!("Include header"), &mut self.include_header) {
             log::info!("Inclusion of SMC header: {}", if self.include_header { "ON" } else { "OFF" });
             let addr_pc = usize::from_str_radix(self.text_pc.to_str(), 16).unwrap_or(0);
@@ -113,11 +134,21 @@ impl UiAddressConverter {
             }
         };
 
+        // Bit 7 makes the LoROM area banks follow the selected blocks too.
+        let sa1_banks = Sa1BankMapping([
+            0x80 | self.sa1_banks[0] as u8,
+            0x80 | self.sa1_banks[1] as u8,
+            0x80 | self.sa1_banks[2] as u8,
+            0x80 | self.sa1_banks[3] as u8,
+        ]);
         let addr_dst = match direction {
             ConvDir::PcToSnes => {
                 let res = match self.conversion_mode {
                     ConversionMode::LoRom => AddrSnes::try_from_lorom(AddrPc(addr_src)),
                     ConversionMode::HiRom => AddrSnes::try_from_hirom(AddrPc(addr_src)),
+                    ConversionMode::ExLoRom => AddrSnes::try_from_exlorom(AddrPc(addr_src)),
+                    ConversionMode::ExHiRom => AddrSnes::try_from_exhirom(AddrPc(addr_src)),
+                    ConversionMode::Sa1 => AddrSnes::try_from_sa1(AddrPc(addr_src), sa1_banks),
                 };
                 match res {
                     Ok(addr) => Ok(addr.0),
@@ -128,6 +159,9 @@ impl UiAddressConverter {
                 let res = match self.conversion_mode {
                     ConversionMode::LoRom => AddrPc::try_from_lorom(AddrSnes(addr_src)),
                     ConversionMode::HiRom => AddrPc::try_from_hirom(AddrSnes(addr_src)),
+                    ConversionMode::ExLoRom => AddrPc::try_from_exlorom(AddrSnes(addr_src)),
+                    ConversionMode::ExHiRom => AddrPc::try_from_exhirom(AddrSnes(addr_src)),
+                    ConversionMode::Sa1 => AddrPc::try_from_sa1(AddrSnes(addr_src), sa1_banks),
                 };
                 match res {
                     Ok(addr) => Ok(addr.0),
@@ -153,6 +187,9 @@ mod modes {
     pub enum ConversionMode {
         LoRom,
         HiRom,
+        ExLoRom,
+        ExHiRom,
+        Sa1,
     }
 
     pub enum ConvDir {
@@ -165,6 +202,9 @@ mod modes {
             f.write_str(match self {
                 ConversionMode::LoRom => "PC ↔ LoRom",
                 ConversionMode::HiRom => "PC ↔ HiRom",
+                ConversionMode::ExLoRom => "PC ↔ ExLoRom",
+                ConversionMode::ExHiRom => "PC ↔ ExHiRom",
+                ConversionMode::Sa1 => "PC ↔ SA-1",
             })
         }
     }
@@ -9,7 +9,14 @@ use glium::{
 };
 use imgui::{im_str, ImString, Image, TextureId, Window};
 use imgui_glium_renderer::Texture;
-use smwe_rom::graphics::{color::Rgba32, gfx_file::N_PIXELS_IN_TILE, palette::ColorPalette};
+use smwe_rom::{
+    graphics::{
+        color::{Abgr1555, Rgba32},
+        gfx_file::N_PIXELS_IN_TILE,
+        palette::ColorPalette,
+    },
+    SmwRom,
+};
 
 use crate::{
     frame_context::FrameContext,
@@ -128,6 +135,40 @@ impl UiGfxViewer {
         }
 
         ctx.ui.text(format!("{} x {} px", self.curr_image_size.0, self.curr_image_size.1));
+
+        if ctx.ui.small_button(im_str!("Export...")) {
+            self.export_gfx_file(ctx);
+        }
+    }
+
+    fn export_gfx_file(&self, ctx: &mut FrameContext) {
+        log::info!("Opened File Selector");
+        use nfd2::Response;
+        if let Response::Okay(path) = nfd2::open_save_dialog(Some("png"), None) //
+            .unwrap_or_else(|e| panic!("Cannot open file selector: {}", e))
+        {
+            let project = ctx.project_ref.as_ref().unwrap().borrow();
+            let rom = &project.rom_data;
+            let gfx_file = &rom.gfx_files[self.curr_gfx_file_num as usize];
+            match gfx_file.export_indexed_png(&path, &self.palette_row(rom)) {
+                Ok(()) => log::info!("Exported GFX file {:X} to {}", self.curr_gfx_file_num, path.display()),
+                Err(e) => log::error!("Cannot export GFX file {:X}: {}", self.curr_gfx_file_num, e),
+            }
+        }
+    }
+
+    fn palette_row(&self, rom: &SmwRom) -> [Abgr1555; 16] {
+        rom.color_palettes
+            .lv_specific_set
+            .palette_from_indices(
+                0,
+                self.curr_bg_palette_num as usize,
+                self.curr_fg_palette_num as usize,
+                self.curr_sp_palette_num as usize,
+                &rom.color_palettes,
+            )
+            .unwrap()
+            .get_row(self.curr_palette_row_idx as usize)
     }
 
     fn gfx_image(&mut self, ctx: &mut FrameContext) {
@@ -192,18 +233,7 @@ impl UiGfxViewer {
             let project = ctx.project_ref.as_ref().unwrap().borrow();
             let rom = &project.rom_data;
             let gfx_file = &rom.gfx_files[self.curr_gfx_file_num as usize];
-            let palette = &rom
-                .color_palettes
-                .lv_specific_set
-                .palette_from_indices(
-                    0,
-                    self.curr_bg_palette_num as usize,
-                    self.curr_fg_palette_num as usize,
-                    self.curr_sp_palette_num as usize,
-                    &rom.color_palettes,
-                )
-                .unwrap()
-                .get_row(self.curr_palette_row_idx as usize);
+            let palette = &self.palette_row(rom);
 
             let img_w = (gfx_file.tiles.len() * 8).clamp(8, N_TILES_IN_ROW * 8);
             let img_h = gfx_file.n_pixels() / (N_TILES_IN_ROW * 8);
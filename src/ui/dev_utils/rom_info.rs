@@ -1,5 +1,5 @@
 use imgui::{ImString, Window};
-use smwe_rom::RomInternalHeader;
+use smwe_rom::{identification::RomIdentity, RomInternalHeader};
 
 use crate::{
     frame_context::FrameContext,
@@ -35,11 +35,13 @@ impl UiTool for UiRomInfo {
 }
 
 impl UiRomInfo {
-    pub fn new(id: WindowId, header: &RomInternalHeader) -> Self {
+    pub fn new(id: WindowId, header: &RomInternalHeader, identity: &RomIdentity) -> Self {
         log::info!("Opened ROM Info");
         UiRomInfo {
             title:        title_with_id("ROM info", id),
             display_data: vec![
+                ImString::new(format!("Identified as:     {}", identity)),
+                ImString::new(format!("CRC32:             {:08X}", identity.crc32)),
                 ImString::new(format!("Internal ROM name: {}", header.internal_rom_name)),
                 ImString::new(format!("Map mode:          {}", header.map_mode)),
                 ImString::new(format!("ROM type:          {}", header.rom_type)),
@@ -48,6 +50,13 @@ impl UiRomInfo {
                 ImString::new(format!("Region:            {}", header.region_code)),
                 ImString::new(format!("Developer ID:      ${:x}", header.developer_id)),
                 ImString::new(format!("Version:           1.{}", header.version_number)),
+                ImString::new(format!("Checksum:          ${:04X} ({})", header.checksum, {
+                    if header.checksum_valid() {
+                        String::from("valid")
+                    } else {
+                        format!("invalid, should be ${:04X}", header.actual_checksum)
+                    }
+                })),
             ],
         }
     }
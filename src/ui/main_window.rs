@@ -61,14 +61,15 @@ impl UiMainWindow {
             if MenuItem::new(im_str!("Address converter")) //
                 .build(ui)
             {
-                self.open_tool(UiAddressConverter::new);
+                let include_header = project.as_ref().map_or(false, |p| p.rom_data.identity.has_smc_header);
+                self.open_tool(|id| UiAddressConverter::new(id, include_header));
             }
             if MenuItem::new(im_str!("ROM info")) //
                 .enabled(project.is_some())
                 .build(ui)
             {
-                let internal_header = &project.as_ref().unwrap().rom_data.internal_header;
-                self.open_tool(|id| UiRomInfo::new(id, internal_header));
+                let rom_data = &project.as_ref().unwrap().rom_data;
+                self.open_tool(|id| UiRomInfo::new(id, &rom_data.internal_header, &rom_data.identity));
             }
             if MenuItem::new(im_str!("Color palettes")) //
                 .enabled(project.is_some())
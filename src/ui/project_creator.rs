@@ -1,9 +1,14 @@
-use std::{cell::RefCell, path::Path, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fs, path::Path, rc::Rc, time::SystemTime};
 
-use imgui::{im_str, ImString, Ui, Window};
+use imgui::{im_str, ComboBox, ImString, Ui, Window};
 use inline_tweak::tweak;
 use smwe_project::Project;
-use smwe_rom::SmwRom;
+use smwe_rom::{
+    identification::RomIdentity,
+    patch::{bps, ips},
+    snes_utils::rom::{Rom, EXPANSION_SIZES},
+    SmwRom,
+};
 
 use crate::{
     frame_context::FrameContext,
@@ -15,10 +20,21 @@ pub struct UiProjectCreator {
 
     project_title: ImString,
     base_rom_path: ImString,
+    patch_path:    ImString,
+    /// Whether the offsets in an IPS patch count from an SMC header, as in most patches for SMW.
+    ips_headered:  bool,
+    expansion:     usize,
 
     err_project_title:    ImString,
     err_base_rom_path:    ImString,
+    err_patch_path:       ImString,
     err_project_creation: ImString,
+
+    warn_base_rom: ImString,
+
+    /// Warnings about ROMs identified so far, by path and modification time, so that typing a path
+    /// doesn't re-read and re-hash the file on every keystroke.
+    base_rom_warnings: HashMap<(String, Option<SystemTime>), ImString>,
 }
 
 impl UiTool for UiProjectCreator {
@@ -35,7 +51,10 @@ impl UiTool for UiProjectCreator {
             .build(ctx.ui, || {
                 self.input_project_title(ctx.ui);
                 self.input_rom_file_path(ctx.ui);
+                self.input_patch_file_path(ctx.ui);
+                self.input_expansion(ctx.ui);
                 self.create_or_cancel(ctx, &mut created_or_cancelled);
+                self.modified_rom_popup(ctx, &mut created_or_cancelled);
                 self.project_error_popup(ctx.ui);
             });
         self.title = title;
@@ -56,10 +75,18 @@ impl UiProjectCreator {
 
             project_title: ImString::new("My SMW hack"),
             base_rom_path: ImString::new(""),
+            patch_path:    ImString::new(""),
+            ips_headered:  true,
+            expansion:     0,
 
             err_project_title:    ImString::new(""),
             err_base_rom_path:    ImString::new(""),
+            err_patch_path:       ImString::new(""),
             err_project_creation: ImString::new(""),
+
+            warn_base_rom: ImString::new(""),
+
+            base_rom_warnings: HashMap::new(),
         };
         myself.handle_rom_file_path();
         myself
@@ -90,12 +117,14 @@ impl UiProjectCreator {
             self.handle_rom_file_path();
         }
         ui.same_line(0.0);
-        if ui.small_button(im_str!("Browse...")) {
+        if ui.small_button(im_str!("Browse...##rom_file")) {
             self.open_file_selector();
         }
 
         if !self.err_base_rom_path.is_empty() {
             ui.text_colored(color::TEXT_ERROR, &self.err_base_rom_path);
+        } else if !self.warn_base_rom.is_empty() {
+            ui.text_colored(color::TEXT_WARNING, &self.warn_base_rom);
         }
     }
 
@@ -107,6 +136,32 @@ impl UiProjectCreator {
             self.err_base_rom_path = ImString::new(format!("'{}' is not a file.", self.base_rom_path));
         } else {
             self.err_base_rom_path.clear();
+            self.identify_base_rom();
+        }
+    }
+
+    fn identify_base_rom(&mut self) {
+        let path = self.base_rom_path.to_str();
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let cache_key = (path.to_owned(), modified);
+        if let Some(warning) = self.base_rom_warnings.get(&cache_key) {
+            self.warn_base_rom = warning.clone();
+            return;
+        }
+
+        self.warn_base_rom.clear();
+        let rom = fs::read(self.base_rom_path.to_str())
+            .map_err(|e| e.to_string())
+            .and_then(|data| Rom::new(data).map_err(|e| e.to_string()));
+        match rom {
+            Ok(rom) => {
+                let identity = RomIdentity::identify(&rom);
+                if !identity.is_clean() {
+                    self.warn_base_rom = ImString::new(format!("Not a recognised clean SMW dump: {}.", identity));
+                }
+                self.base_rom_warnings.insert(cache_key, self.warn_base_rom.clone());
+            }
+            Err(err) => self.err_base_rom_path = ImString::new(format!("Cannot load ROM: {}", err)),
         }
     }
 
@@ -121,11 +176,71 @@ impl UiProjectCreator {
         }
     }
 
+    fn input_patch_file_path(&mut self, ui: &Ui) {
+        ui.text(im_str!("Patch to apply (optional):"));
+        if ui.input_text(im_str!("##patch_file"), &mut self.patch_path).build() {
+            self.handle_patch_file_path();
+        }
+        ui.same_line(0.0);
+        if ui.small_button(im_str!("Browse...##patch_file")) {
+            self.open_patch_file_selector();
+        }
+        if !self.patch_path.is_empty()
+            && ui.checkbox(im_str!("IPS patch made for a headered ROM"), &mut self.ips_headered)
+        {
+            log::info!("IPS offsets include SMC header: {}", if self.ips_headered { "ON" } else { "OFF" });
+        }
+
+        if !self.err_patch_path.is_empty() {
+            ui.text_colored(color::TEXT_ERROR, &self.err_patch_path);
+        }
+    }
+
+    fn handle_patch_file_path(&mut self) {
+        let file_path = Path::new(self.patch_path.to_str());
+        if self.patch_path.is_empty() {
+            self.err_patch_path.clear();
+        } else if !file_path.exists() {
+            self.err_patch_path = ImString::new(format!("File '{}' does not exist.", self.patch_path));
+        } else if file_path.is_dir() {
+            self.err_patch_path = ImString::new(format!("'{}' is not a file.", self.patch_path));
+        } else {
+            self.err_patch_path.clear();
+        }
+    }
+
+    fn open_patch_file_selector(&mut self) {
+        log::info!("Opened File Selector");
+        use nfd2::Response;
+        if let Response::Okay(path) = nfd2::open_file_dialog(Some("bps,ips"), None) //
+            .unwrap_or_else(|e| panic!("Cannot open file selector: {}", e))
+        {
+            self.patch_path = ImString::new(path.to_str().unwrap());
+            self.handle_patch_file_path();
+        }
+    }
+
+    fn input_expansion(&mut self, ui: &Ui) {
+        ui.text(im_str!("Expand ROM to:"));
+        ComboBox::new(im_str!("##expansion")).build_simple_string(ui, &mut self.expansion, &[
+            im_str!("Don't expand"),
+            im_str!("1 MB"),
+            im_str!("2 MB"),
+            im_str!("4 MB"),
+            im_str!("8 MB (ExLoROM)"),
+        ]);
+    }
+
     fn create_or_cancel(&mut self, ctx: &mut FrameContext, created_or_cancelled: &mut bool) {
         if self.no_creation_errors() {
             if ctx.ui.small_button(im_str!("Create")) {
-                log::info!("Attempting to create a new project");
-                self.handle_project_creation(ctx, created_or_cancelled);
+                if self.warn_base_rom.is_empty() {
+                    log::info!("Attempting to create a new project");
+                    self.handle_project_creation(ctx, created_or_cancelled);
+                } else {
+                    log::info!("Base ROM is not clean, asking for confirmation");
+                    ctx.ui.open_popup(im_str!("Warning!##modified_rom"));
+                }
             }
         } else {
             ctx.ui.text_disabled(im_str!("Create"));
@@ -138,7 +253,7 @@ impl UiProjectCreator {
     }
 
     fn handle_project_creation(&mut self, ctx: &mut FrameContext, created_or_cancelled: &mut bool) {
-        match SmwRom::from_file(self.base_rom_path.to_str()) {
+        match self.load_rom() {
             Ok(rom_data) => {
                 log::info!("Success creating a new project");
                 let project = Project { title: self.project_title.to_string(), rom_data };
@@ -148,12 +263,62 @@ impl UiProjectCreator {
             }
             Err(err) => {
                 log::info!("Failed to create a new project: {}", err);
-                self.err_project_creation = ImString::from(err.to_string());
+                self.err_project_creation = ImString::from(err);
                 ctx.ui.open_popup(im_str!("Error!##project_error"));
             }
         }
     }
 
+    fn load_rom(&self) -> Result<SmwRom, String> {
+        let rom_data = fs::read(self.base_rom_path.to_str()).map_err(|e| format!("Cannot read ROM: {}", e))?;
+        let mut rom = Rom::new(rom_data).map_err(|e| e.to_string())?;
+        if !self.patch_path.is_empty() {
+            log::info!("Applying patch '{}' to '{}'", self.patch_path, self.base_rom_path);
+            let patch = fs::read(self.patch_path.to_str()).map_err(|e| format!("Cannot read patch: {}", e))?;
+            if patch.starts_with(b"BPS1") {
+                bps::apply(&mut rom, &patch).map_err(|e| format!("Cannot apply BPS patch:\n- {}", e))?;
+            } else {
+                let offsets = if self.ips_headered { ips::Offsets::Headered } else { ips::Offsets::Headerless };
+                ips::apply(&mut rom, &patch, offsets).map_err(|e| format!("Cannot apply IPS patch:\n- {}", e))?;
+            }
+            rom.detect_mapper();
+        }
+        if self.expansion > 0 {
+            let size = EXPANSION_SIZES[self.expansion - 1];
+            if size > rom.len() {
+                rom.expand(size).map_err(|e| format!("Cannot expand ROM:\n- {}", e))?;
+            } else {
+                log::info!("ROM is already {:#X} bytes long, not expanding", rom.len());
+            }
+        }
+        SmwRom::from_rom(rom).map_err(|e| e.to_string())
+    }
+
+    fn modified_rom_popup(&mut self, ctx: &mut FrameContext, created_or_cancelled: &mut bool) {
+        let ui = ctx.ui;
+        let mut confirmed = false;
+        ui.popup_modal(im_str!("Warning!##modified_rom"))
+            .always_auto_resize(true)
+            .resizable(false)
+            .collapsible(false)
+            .build(|| {
+                ui.text_wrapped(&self.warn_base_rom);
+                ui.text_wrapped(im_str!("Create the project on top of it anyway?"));
+                if ui.button(im_str!("Create anyway"), [tweak!(145.0), tweak!(20.0)]) {
+                    confirmed = true;
+                    ui.close_current_popup();
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Cancel"), [tweak!(145.0), tweak!(20.0)]) {
+                    ui.close_current_popup();
+                }
+            });
+        if confirmed {
+            log::info!("Attempting to create a new project on top of an unrecognised ROM");
+            self.handle_project_creation(ctx, created_or_cancelled);
+        }
+    }
+
     fn project_error_popup(&self, ui: &Ui) {
         ui.popup_modal(im_str!("Error!##project_error"))
             .always_auto_resize(true)
@@ -168,6 +333,6 @@ impl UiProjectCreator {
     }
 
     fn no_creation_errors(&self) -> bool {
-        vec![&self.err_base_rom_path, &self.err_project_title].iter().all(|s| s.is_empty())
+        vec![&self.err_base_rom_path, &self.err_patch_path, &self.err_project_title].iter().all(|s| s.is_empty())
     }
 }